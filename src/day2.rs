@@ -1,6 +1,10 @@
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::{collections::BTreeMap, fmt};
+
+type Colour = String;
+type Bag = BTreeMap<Colour, u32>;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Game {
@@ -9,15 +13,16 @@ pub struct Game {
 }
 
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
-pub struct Set {
-    red: u32,
-    green: u32,
-    blue: u32,
-}
+pub struct Set(BTreeMap<Colour, u32>);
 
 lazy_static! {
     static ref GAME_ID_RE: Regex = Regex::new(r"Game (\d+)").unwrap();
-    static ref DICE_RE: Regex = Regex::new(r"(\d+) (blue|red|green)").unwrap();
+    static ref DICE_RE: Regex = Regex::new(r"(\d+) (\w+)").unwrap();
+    static ref TOKEN_RE: Regex = Regex::new(r"\d+|\w+|>=|<=|==|!=|[<>()]|\S").unwrap();
+    static ref BAG: Bag = [("red", 12), ("green", 13), ("blue", 14)]
+        .into_iter()
+        .map(|(colour, max)| (colour.to_string(), max))
+        .collect();
 }
 
 fn parse_game(input: &str) -> Game {
@@ -33,18 +38,7 @@ fn parse_game(input: &str) -> Game {
                 .captures_iter(set)
                 .fold(Set::default(), |mut set, cap| {
                     let num = cap[1].parse().unwrap();
-                    match &cap[2] {
-                        "red" => {
-                            set.red = num;
-                        }
-                        "green" => {
-                            set.green = num;
-                        }
-                        "blue" => {
-                            set.blue = num;
-                        }
-                        _ => unreachable!(),
-                    };
+                    set.0.insert(cap[2].to_string(), num);
                     set
                 })
         })
@@ -57,19 +51,46 @@ pub fn generator(input: &str) -> Vec<Game> {
     input.lines().map(parse_game).collect_vec()
 }
 
-static MAX_RED: u32 = 12;
-static MAX_GREEN: u32 = 13;
-static MAX_BLUE: u32 = 14;
+#[derive(Debug, PartialEq, Eq)]
+pub struct Violation {
+    set: usize,
+    colour: Colour,
+    count: u32,
+    limit: u32,
+}
+
+// colours missing from the bag have a limit of 0
+fn violations(game: &Game, bag: &Bag) -> Vec<Violation> {
+    game.sets
+        .iter()
+        .enumerate()
+        .flat_map(|(set_index, set)| {
+            set.0.iter().filter_map(move |(colour, &count)| {
+                let limit = bag.get(colour).copied().unwrap_or(0);
+                if count > limit {
+                    Some(Violation {
+                        set: set_index,
+                        colour: colour.clone(),
+                        count,
+                        limit,
+                    })
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+fn possible(game: &Game, bag: &Bag) -> bool {
+    violations(game, bag).is_empty()
+}
 
 pub fn part_1(input: &[Game]) -> u32 {
     input
         .iter()
         .filter_map(|game| {
-            if game
-                .sets
-                .iter()
-                .all(|set| set.red <= MAX_RED && set.green <= MAX_GREEN && set.blue <= MAX_BLUE)
-            {
+            if possible(game, &BAG) {
                 Some(game.id)
             } else {
                 None
@@ -78,22 +99,466 @@ pub fn part_1(input: &[Game]) -> u32 {
         .sum()
 }
 
-fn game_power(game: &Game) -> u32 {
-    let max = game.sets.iter().fold(Set::default(), |acc, set| Set {
-        red: acc.red.max(set.red),
-        green: acc.green.max(set.green),
-        blue: acc.blue.max(set.blue),
+fn merge_bags(mut acc: Bag, bag: &Bag) -> Bag {
+    bag.iter().for_each(|(colour, &num)| {
+        let max = acc.entry(colour.clone()).or_default();
+        *max = (*max).max(num);
     });
-    max.red * max.green * max.blue
+    acc
+}
+
+fn min_bag(game: &Game) -> Bag {
+    game.sets
+        .iter()
+        .fold(Bag::new(), |acc, set| merge_bags(acc, &set.0))
+}
+
+fn game_power(game: &Game) -> u32 {
+    min_bag(game).values().product()
 }
 
 pub fn part_2(input: &[Game]) -> u32 {
     input.iter().map(game_power).sum()
 }
 
+// ids of the games that are impossible with `bag` but possible once `colour` is raised by `raise`
+fn unlocked_by(input: &[Game], bag: &Bag, colour: &str, raise: u32) -> Vec<u32> {
+    let mut raised = bag.clone();
+    let limit = raised.entry(colour.to_string()).or_default();
+    *limit = limit.saturating_add(raise);
+    input
+        .iter()
+        .filter(|game| !possible(game, bag) && possible(game, &raised))
+        .map(|game| game.id)
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct GameReport {
+    id: u32,
+    min_bag: Bag,
+    violations: Vec<Violation>,
+}
+
+#[derive(Debug)]
+pub struct Report {
+    games: Vec<GameReport>,
+    min_bag: Bag,
+    raise: u32,
+    unlocked: BTreeMap<Colour, Vec<u32>>,
+}
+
+fn build_report(input: &[Game], bag: &Bag, raise: u32) -> Report {
+    let games = input
+        .iter()
+        .map(|game| GameReport {
+            id: game.id,
+            min_bag: min_bag(game),
+            violations: violations(game, bag),
+        })
+        .collect_vec();
+    let min_bag = games
+        .iter()
+        .fold(Bag::new(), |acc, game| merge_bags(acc, &game.min_bag));
+    // colours only seen in the games start at a limit of 0 but can still unlock them
+    let unlocked = bag
+        .keys()
+        .chain(
+            input
+                .iter()
+                .flat_map(|game| game.sets.iter().flat_map(|set| set.0.keys())),
+        )
+        .unique()
+        .map(|colour| (colour.clone(), unlocked_by(input, bag, colour, raise)))
+        .collect();
+
+    Report {
+        games,
+        min_bag,
+        raise,
+        unlocked,
+    }
+}
+
+// The raise is read from `DAY2_RAISE`, e.g. `DAY2_RAISE=3 cargo run -- -d 2`
+pub fn report(input: &[Game]) -> String {
+    let raise = match std::env::var("DAY2_RAISE") {
+        Ok(raise) => match raise.parse() {
+            Ok(raise) => raise,
+            Err(err) => return format!("invalid DAY2_RAISE: {err}"),
+        },
+        Err(_) => 1,
+    };
+    build_report(input, &BAG, raise).to_string()
+}
+
+fn fmt_bag(bag: &Bag) -> String {
+    bag.iter()
+        .map(|(colour, num)| format!("{num} {colour}"))
+        .join(", ")
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for game in &self.games {
+            let state = if game.violations.is_empty() {
+                "possible"
+            } else {
+                "impossible"
+            };
+            writeln!(
+                f,
+                "Game {}: {state}, min bag {}",
+                game.id,
+                fmt_bag(&game.min_bag)
+            )?;
+            for violation in &game.violations {
+                writeln!(
+                    f,
+                    "  set {}: {} {} > {}",
+                    violation.set + 1,
+                    violation.count,
+                    violation.colour,
+                    violation.limit
+                )?;
+            }
+        }
+        write!(f, "Min bag for all games: {}", fmt_bag(&self.min_bag))?;
+        for (colour, ids) in &self.unlocked {
+            write!(f, "\n+{} {colour} unlocks games {:?}", self.raise, ids)?;
+        }
+        Ok(())
+    }
+}
+
+// 95% two-sided normal quantile
+static Z_95: f64 = 1.96;
+
+#[derive(Debug)]
+pub struct Estimate {
+    colour: Colour,
+    draws: u32,
+    proportion: f64,
+    lower: f64,
+    upper: f64,
+}
+
+// Wilson score interval for `k` successes out of `n` draws
+fn wilson(k: u32, n: u32, z: f64) -> (f64, f64) {
+    let n = n as f64;
+    let p = k as f64 / n;
+    let z2 = z * z;
+    let denom = 1.0 + z2 / n;
+    let centre = (p + z2 / (2.0 * n)) / denom;
+    let half = z / denom * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((centre - half).max(0.0), (centre + half).min(1.0))
+}
+
+// Treats every cube shown in a game as an independent draw (with replacement)
+// from the hidden bag, so the MLE of each colour's share is its observed frequency.
+fn estimate_game(game: &Game, z: f64) -> Vec<Estimate> {
+    let draws = game.sets.iter().fold(Bag::new(), |mut acc, set| {
+        set.0.iter().for_each(|(colour, &num)| {
+            *acc.entry(colour.clone()).or_default() += num;
+        });
+        acc
+    });
+    let total: u32 = draws.values().sum();
+    if total == 0 {
+        return vec![];
+    }
+
+    draws
+        .into_iter()
+        .map(|(colour, k)| {
+            let (lower, upper) = wilson(k, total, z);
+            Estimate {
+                colour,
+                draws: k,
+                proportion: k as f64 / total as f64,
+                lower,
+                upper,
+            }
+        })
+        .collect()
+}
+
+pub fn estimate(input: &[Game]) -> String {
+    input
+        .iter()
+        .map(|game| {
+            let estimates = estimate_game(game, Z_95)
+                .iter()
+                .map(|est| {
+                    format!(
+                        "  {} {}: {:.3} [{:.3}, {:.3}]",
+                        est.draws, est.colour, est.proportion, est.lower, est.upper
+                    )
+                })
+                .join("\n");
+            format!("Game {}:\n{estimates}", game.id)
+        })
+        .join("\n")
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Agg {
+    Max,
+    Min,
+    Sum,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Value {
+    Num(u32),
+    Id,
+    Sets,
+    Agg(Agg, Colour),
+    // only valid inside any(..)/all(..), evaluated against a single set
+    Count(Colour),
+    Total,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Op {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Query {
+    Cmp(Value, Op, Value),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Any(Box<Query>),
+    All(Box<Query>),
+}
+
+// query := or
+// or    := and ("or" and)*
+// and   := unary ("and" unary)*
+// unary := "not" unary | "(" query ")" | ("any" | "all") "(" query ")" | value op value
+// value := number | "id" | "sets" | ("max" | "min" | "sum") "(" colour ")" | colour | "total"
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    in_set: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let token = self.peek().ok_or("unexpected end of query")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected `{expected}`, found `{token}`")),
+        }
+    }
+
+    fn or(&mut self) -> Result<Query, String> {
+        let mut query = self.and()?;
+        while self.peek() == Some("or") {
+            self.pos += 1;
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut query = self.unary()?;
+        while self.peek() == Some("and") {
+            self.pos += 1;
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+        Ok(query)
+    }
+
+    fn unary(&mut self) -> Result<Query, String> {
+        match self.peek() {
+            Some("not") => {
+                self.pos += 1;
+                Ok(Query::Not(Box::new(self.unary()?)))
+            }
+            Some("(") => {
+                self.pos += 1;
+                let query = self.or()?;
+                self.expect(")")?;
+                Ok(query)
+            }
+            Some(quantifier @ ("any" | "all")) => {
+                if self.in_set {
+                    return Err(format!("`{quantifier}` cannot be nested"));
+                }
+                self.pos += 1;
+                self.expect("(")?;
+                self.in_set = true;
+                let query = self.or()?;
+                self.in_set = false;
+                self.expect(")")?;
+                Ok(match quantifier {
+                    "any" => Query::Any(Box::new(query)),
+                    _ => Query::All(Box::new(query)),
+                })
+            }
+            _ => {
+                let left = self.value()?;
+                let op = match self.next()? {
+                    ">" => Op::Gt,
+                    ">=" => Op::Ge,
+                    "<" => Op::Lt,
+                    "<=" => Op::Le,
+                    "==" => Op::Eq,
+                    "!=" => Op::Ne,
+                    token => return Err(format!("expected comparison, found `{token}`")),
+                };
+                Ok(Query::Cmp(left, op, self.value()?))
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let token = self.next()?;
+        if let Ok(num) = token.parse() {
+            return Ok(Value::Num(num));
+        }
+
+        let value = match token {
+            "id" => Value::Id,
+            "sets" => Value::Sets,
+            "max" | "min" | "sum" => {
+                let agg = match token {
+                    "max" => Agg::Max,
+                    "min" => Agg::Min,
+                    _ => Agg::Sum,
+                };
+                self.expect("(")?;
+                let colour = self.colour()?;
+                self.expect(")")?;
+                Value::Agg(agg, colour)
+            }
+            "total" => Value::Total,
+            _ => {
+                self.pos -= 1;
+                Value::Count(self.colour()?)
+            }
+        };
+
+        match value {
+            Value::Count(_) | Value::Total if !self.in_set => {
+                Err(format!("`{token}` is only valid inside any(..) or all(..)"))
+            }
+            Value::Id | Value::Sets | Value::Agg(_, _) if self.in_set => {
+                Err(format!("`{token}` is not valid inside any(..) or all(..)"))
+            }
+            value => Ok(value),
+        }
+    }
+
+    fn colour(&mut self) -> Result<Colour, String> {
+        match self.next()? {
+            token if token.chars().all(|c| c.is_alphabetic()) => Ok(token.to_string()),
+            token => Err(format!("expected colour, found `{token}`")),
+        }
+    }
+}
+
+fn parse_query(input: &str) -> Result<Query, String> {
+    let mut parser = Parser {
+        tokens: TOKEN_RE.find_iter(input).map(|m| m.as_str()).collect(),
+        pos: 0,
+        in_set: false,
+    };
+    let query = parser.or()?;
+    match parser.peek() {
+        Some(token) => Err(format!("unexpected `{token}`")),
+        None => Ok(query),
+    }
+}
+
+fn eval_value(value: &Value, game: &Game, set: Option<&Set>) -> u32 {
+    match value {
+        Value::Num(num) => *num,
+        Value::Id => game.id,
+        Value::Sets => game.sets.len() as u32,
+        Value::Agg(agg, colour) => {
+            let counts = game
+                .sets
+                .iter()
+                .map(|set| set.0.get(colour).copied().unwrap_or(0));
+            match agg {
+                Agg::Max => counts.max().unwrap_or(0),
+                Agg::Min => counts.min().unwrap_or(0),
+                Agg::Sum => counts.sum(),
+            }
+        }
+        Value::Count(colour) => set.and_then(|set| set.0.get(colour)).copied().unwrap_or(0),
+        Value::Total => set.map(|set| set.0.values().sum()).unwrap_or(0),
+    }
+}
+
+fn eval(query: &Query, game: &Game, set: Option<&Set>) -> bool {
+    match query {
+        Query::Cmp(left, op, right) => {
+            let left = eval_value(left, game, set);
+            let right = eval_value(right, game, set);
+            match op {
+                Op::Gt => left > right,
+                Op::Ge => left >= right,
+                Op::Lt => left < right,
+                Op::Le => left <= right,
+                Op::Eq => left == right,
+                Op::Ne => left != right,
+            }
+        }
+        Query::Not(query) => !eval(query, game, set),
+        Query::And(a, b) => eval(a, game, set) && eval(b, game, set),
+        Query::Or(a, b) => eval(a, game, set) || eval(b, game, set),
+        Query::Any(query) => game.sets.iter().any(|set| eval(query, game, Some(set))),
+        Query::All(query) => game.sets.iter().all(|set| eval(query, game, Some(set))),
+    }
+}
+
+fn filter_games<'a>(input: &'a [Game], query: &Query) -> Vec<&'a Game> {
+    input
+        .iter()
+        .filter(|game| eval(query, game, None))
+        .collect()
+}
+
+// The runner only hands over the parsed input, so the query is read from `DAY2_QUERY`,
+// e.g. `DAY2_QUERY="sets > 3 and any(blue > 10)" cargo run -- -d 2`
+pub fn query(input: &[Game]) -> String {
+    let Ok(query) = std::env::var("DAY2_QUERY") else {
+        return "set DAY2_QUERY to run a query".to_string();
+    };
+    match parse_query(&query) {
+        Ok(query) => {
+            let ids = filter_games(input, &query)
+                .iter()
+                .map(|game| game.id)
+                .collect_vec();
+            format!("{} games {:?}", ids.len(), ids)
+        }
+        Err(err) => format!("invalid query: {err}"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use insta::assert_debug_snapshot;
+    use insta::{assert_debug_snapshot, assert_snapshot};
 
     use super::*;
 
@@ -138,4 +603,118 @@ mod tests {
         let input = generator(&INPUT);
         assert_eq!(part_2(&input), 2286);
     }
+
+    #[test]
+    fn test_other_colours() {
+        let game = parse_game("Game 7: 2 yellow, 1 red; 3 purple, 4 yellow; 1 red, 2 purple");
+        assert_eq!(game.sets[1].0["yellow"], 4);
+        assert!(!game.sets[1].0.contains_key("red"));
+        assert_eq!(game_power(&game), 12);
+        assert!(!possible(&game, &BAG));
+
+        let mut bag = BAG.clone();
+        bag.insert("yellow".to_string(), 4);
+        bag.insert("purple".to_string(), 3);
+        assert!(possible(&game, &bag));
+    }
+
+    #[test]
+    fn test_violations() {
+        let game =
+            parse_game("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red");
+        assert_eq!(
+            violations(&game, &BAG),
+            vec![Violation {
+                set: 0,
+                colour: "red".to_string(),
+                count: 20,
+                limit: 12
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unlocked_by() {
+        let input = generator(&INPUT);
+        assert!(unlocked_by(&input, &BAG, "blue", 1).is_empty());
+        assert_eq!(unlocked_by(&input, &BAG, "red", 8), vec![3]);
+
+        let mut bag = BAG.clone();
+        bag.insert("red".to_string(), 14);
+        assert_eq!(unlocked_by(&input, &bag, "blue", 1), vec![4]);
+        assert_eq!(unlocked_by(&input, &BAG, "red", u32::MAX), vec![3]);
+    }
+
+    #[test]
+    fn test_report() {
+        let input = generator(&INPUT);
+        assert_snapshot!(build_report(&input, &BAG, 1).to_string());
+    }
+
+    #[test]
+    fn test_report_unlocks_unseen_colours() {
+        let input = generator(
+            "Game 1: 2 yellow, 1 red; 1 blue
+    Game 2: 14 red, 1 blue
+    Game 3: 1 green",
+        );
+        let report = build_report(&input, &BAG, 2);
+        assert_eq!(report.unlocked["yellow"], vec![1]);
+        assert_eq!(report.unlocked["red"], vec![2]);
+        assert!(report.unlocked["green"].is_empty());
+    }
+
+    #[test]
+    fn test_estimate_game() {
+        let game = parse_game("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green");
+        let estimates = estimate_game(&game, Z_95);
+        assert_eq!(
+            estimates.iter().map(|est| est.draws).collect_vec(),
+            vec![9, 4, 5]
+        );
+        assert_eq!(estimates[0].proportion, 0.5);
+        estimates.iter().for_each(|est| {
+            assert!(est.lower < est.proportion && est.proportion < est.upper);
+            assert!(est.lower >= 0.0 && est.upper <= 1.0);
+        });
+
+        let (lower, upper) = wilson(0, 10, Z_95);
+        assert_eq!(lower, 0.0);
+        assert!(upper > 0.0 && upper < 0.35);
+
+        let game = parse_game("Game 2: ; ");
+        assert!(estimate_game(&game, Z_95).is_empty());
+    }
+
+    #[test]
+    fn test_estimate() {
+        let input = generator(&INPUT);
+        assert_snapshot!(estimate(&input[..2]));
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_debug_snapshot!(parse_query("sets > 3 and any(blue > 10) or not (id == 2)"));
+        assert!(parse_query("blue > 10").is_err());
+        assert!(parse_query("any(sets > 1)").is_err());
+        assert!(parse_query("any(all(red > 1))").is_err());
+        assert!(parse_query("max(red) >").is_err());
+        assert!(parse_query("max(red) > 1 )").is_err());
+    }
+
+    #[test]
+    fn test_filter_games() {
+        let input = generator(&INPUT);
+        let ids = |query: &str| {
+            filter_games(&input, &parse_query(query).unwrap())
+                .iter()
+                .map(|game| game.id)
+                .collect_vec()
+        };
+        assert_eq!(ids("sets >= 3 and any(blue > 5)"), vec![1, 3, 4]);
+        assert_eq!(ids("max(red) > 12 or sum(green) == 5"), vec![3, 4, 5]);
+        assert_eq!(ids("all(total <= 8)"), vec![2]);
+        assert_eq!(ids("min(red) == 0 and not id == 1"), vec![2]);
+        assert_eq!(ids("any(red >= blue and green > 0)"), vec![1, 3, 4, 5]);
+    }
 }
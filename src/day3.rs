@@ -1,54 +1,11 @@
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::{collections::HashMap, ops::Range};
+use std::{collections::HashMap, ops::Range, str::FromStr};
 
 lazy_static! {
     static ref NUM_REG: Regex = Regex::new(r"\d+").unwrap();
-    static ref SYMBOLS_REG: Regex = Regex::new(r"[^\d\.\n]").unwrap();
-    static ref GEARS_REG: Regex = Regex::new(r"\*").unwrap();
-}
-
-pub fn part_1(input: &str) -> u32 {
-    let lines = input.lines().collect_vec();
-    input
-        .lines()
-        .enumerate()
-        .flat_map(parse_parts)
-        .filter_map(|part| {
-            if part.row > 0 {
-                let len = lines[part.row - 1].len() - 1;
-                let section = lines[part.row - 1]
-                    [(part.pos.start.max(1) - 1)..(part.pos.end + 1).min(len)]
-                    .to_string();
-                if SYMBOLS_REG.is_match(&section) {
-                    return Some(part.id);
-                }
-            }
-
-            {
-                let len = lines[part.row].len() - 1;
-                let section = lines[part.row]
-                    [(part.pos.start.max(1) - 1)..(part.pos.end + 1).min(len)]
-                    .to_string();
-                if SYMBOLS_REG.is_match(&section) {
-                    return Some(part.id);
-                }
-            }
-
-            if part.row != lines.len() - 1 {
-                let len = lines[part.row + 1].len() - 1;
-                let section = lines[part.row + 1]
-                    [(part.pos.start.max(1) - 1)..(part.pos.end + 1).min(len)]
-                    .to_string();
-                if SYMBOLS_REG.is_match(&section) {
-                    return Some(part.id);
-                }
-            }
-
-            None
-        })
-        .sum()
+    static ref SYMBOLS_REG: Regex = Regex::new(r"[^\d\.\s]").unwrap();
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -58,6 +15,23 @@ struct Part {
     pos: Range<usize>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Gear {
+    symbol: char,
+    row: usize,
+    col: usize,
+}
+
+#[derive(Debug)]
+pub struct Schematic {
+    lines: Vec<String>,
+    parts: Vec<Part>,
+    gears: Vec<Gear>,
+    // indexes into `gears` for every part, and into `parts` for every gear
+    part_gears: Vec<Vec<usize>>,
+    gear_parts: Vec<Vec<usize>>,
+}
+
 fn parse_parts((row, line): (usize, &str)) -> Vec<Part> {
     NUM_REG
         .captures_iter(line)
@@ -72,86 +46,430 @@ fn parse_parts((row, line): (usize, &str)) -> Vec<Part> {
         .collect()
 }
 
-pub fn part_2(input: &str) -> u32 {
-    let parts_by_row: HashMap<usize, Vec<Part>> = input
+fn parse_gears((row, line): (usize, &str)) -> Vec<Gear> {
+    SYMBOLS_REG
+        .find_iter(line)
+        .map(|symbol| Gear {
+            symbol: symbol.as_str().chars().next().unwrap(),
+            row,
+            col: symbol.start(),
+        })
+        .collect()
+}
+
+// Cells surrounding a part, without stepping below 0 on either axis.
+fn neighbours(part: &Part) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (part.row.saturating_sub(1)..=part.row + 1)
+        .cartesian_product(part.pos.start.saturating_sub(1)..=part.pos.end)
+        .filter(move |&(row, col)| row != part.row || !part.pos.contains(&col))
+}
+
+pub fn generator(input: &str) -> Schematic {
+    let parts = input
         .lines()
         .enumerate()
         .flat_map(parse_parts)
-        .group_by(|part| part.row)
-        .into_iter()
-        .fold(HashMap::new(), |mut acc, (row, parts)| {
-            acc.insert(row, parts.collect_vec());
-            acc
-        });
-
+        .collect_vec();
     let gears = input
         .lines()
         .enumerate()
         .flat_map(parse_gears)
         .collect_vec();
 
-    gears
+    let gears_by_pos: HashMap<(usize, usize), usize> = gears
         .iter()
-        .filter_map(|gear| {
-            let mut found_parts = vec![];
-            let row = gear.row;
-
-            // Row before
-            if let Some(section) = parts_by_row.get(&(row - 1)) {
-                section.iter().for_each(|part| {
-                    if part.pos.start <= gear.col + 1 && gear.col <= part.pos.end {
-                        found_parts.push(part.clone());
-                    }
-                });
-            }
+        .enumerate()
+        .map(|(index, gear)| ((gear.row, gear.col), index))
+        .collect();
 
-            // Same row
-            if let Some(section) = parts_by_row.get(&row) {
-                // println!("cur {:?}", &section);
-                section.iter().for_each(|part| {
-                    if part.pos.start <= gear.col + 1 && gear.col <= part.pos.end {
-                        found_parts.push(part.clone());
-                    }
-                });
-            }
+    let mut gear_parts = vec![vec![]; gears.len()];
+    let part_gears = parts
+        .iter()
+        .enumerate()
+        .map(|(part_index, part)| {
+            neighbours(part)
+                .filter_map(|pos| gears_by_pos.get(&pos).copied())
+                .inspect(|&gear_index| gear_parts[gear_index].push(part_index))
+                .collect_vec()
+        })
+        .collect();
+
+    Schematic {
+        lines: input.lines().map(str::to_string).collect(),
+        parts,
+        gears,
+        part_gears,
+        gear_parts,
+    }
+}
+
+pub fn part_1(input: &Schematic) -> u32 {
+    input
+        .parts
+        .iter()
+        .zip(&input.part_gears)
+        .filter(|(_, gears)| !gears.is_empty())
+        .map(|(part, _)| part.id)
+        .sum()
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Count {
+    Exactly(usize),
+    AtLeast(usize),
+}
 
-            // Row after
-            if let Some(section) = parts_by_row.get(&(row + 1)) {
-                section.iter().for_each(|part| {
-                    if part.pos.start <= gear.col + 1 && gear.col <= part.pos.end {
-                        found_parts.push(part.clone());
-                    }
-                });
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Aggregate {
+    Product,
+    Sum,
+    Max,
+}
+
+// Which symbols count as a component, how many parts they must touch and how
+// those parts combine, written as e.g. `* ==2 product` or `#$ >=3 sum`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Rule {
+    // empty matches every symbol
+    symbols: Vec<char>,
+    count: Count,
+    aggregate: Aggregate,
+}
+
+impl Rule {
+    fn gear_ratio() -> Self {
+        Rule {
+            symbols: vec!['*'],
+            count: Count::Exactly(2),
+            aggregate: Aggregate::Product,
+        }
+    }
+
+    fn matches(&self, gear: &Gear, parts: usize) -> bool {
+        (self.symbols.is_empty() || self.symbols.contains(&gear.symbol))
+            && match self.count {
+                Count::Exactly(count) => parts == count,
+                Count::AtLeast(count) => parts >= count,
             }
+    }
+}
 
-            // dbg!(&gear, &found_parts);
-            if found_parts.len() == 2 {
-                Some(found_parts.iter().map(|part| part.id).product::<u32>())
-            } else {
-                None
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [symbols, count, aggregate] = s.split_whitespace().collect_vec()[..] else {
+            return Err(format!(
+                "expected `<symbols> <count> <aggregate>`, got `{s}`"
+            ));
+        };
+
+        let symbols = match symbols {
+            "any" => vec![],
+            symbols => symbols.chars().collect(),
+        };
+        let count = if let Some(count) = count.strip_prefix(">=") {
+            count.parse().map(Count::AtLeast)
+        } else {
+            count.trim_start_matches("==").parse().map(Count::Exactly)
+        }
+        .map_err(|_| format!("invalid count `{count}`"))?;
+        let aggregate = match aggregate {
+            "product" => Aggregate::Product,
+            "sum" => Aggregate::Sum,
+            "max" => Aggregate::Max,
+            aggregate => return Err(format!("invalid aggregate `{aggregate}`")),
+        };
+
+        Ok(Rule {
+            symbols,
+            count,
+            aggregate,
+        })
+    }
+}
+
+// Rules touching many parts multiply quickly, so overflow is reported rather than wrapped.
+fn evaluate(input: &Schematic, rule: &Rule) -> Result<u64, String> {
+    let overflow = || "rule result overflows u64".to_string();
+    input
+        .gears
+        .iter()
+        .zip(&input.gear_parts)
+        .filter(|(gear, parts)| rule.matches(gear, parts.len()))
+        .map(|(_, parts)| {
+            let mut ids = parts.iter().map(|&part| input.parts[part].id as u64);
+            match rule.aggregate {
+                Aggregate::Product => ids.try_fold(1_u64, u64::checked_mul).ok_or_else(overflow),
+                Aggregate::Sum => ids.try_fold(0_u64, u64::checked_add).ok_or_else(overflow),
+                Aggregate::Max => Ok(ids.max().unwrap_or(0)),
             }
         })
-        .sum()
+        .try_fold(0_u64, |acc, value| {
+            acc.checked_add(value?).ok_or_else(overflow)
+        })
+}
+
+pub fn part_2(input: &Schematic) -> Result<u64, String> {
+    evaluate(input, &Rule::gear_ratio())
+}
+
+// The rule is read from `DAY3_RULE`, e.g. `DAY3_RULE="# >=1 sum" cargo run -- -d 3`
+pub fn rule(input: &Schematic) -> String {
+    let Ok(rule) = std::env::var("DAY3_RULE") else {
+        return "set DAY3_RULE to evaluate a rule".to_string();
+    };
+    match rule.parse::<Rule>() {
+        Ok(rule) => match evaluate(input, &rule) {
+            Ok(value) => value.to_string(),
+            Err(err) => err,
+        },
+        Err(err) => format!("invalid rule: {err}"),
+    }
+}
+
+// Union by size keeps the trees shallow and `find` walks them iteratively, so
+// long chains of parts cannot overflow the stack.
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        UnionFind {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, node: usize) -> usize {
+        let mut root = node;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut node = node;
+        while node != root {
+            node = std::mem::replace(&mut self.parent[node], root);
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        let (small, large) = if self.size[a] < self.size[b] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        self.parent[small] = large;
+        self.size[large] += self.size[small];
+    }
 }
 
 #[derive(Debug)]
-struct Gear {
-    row: usize,
-    col: usize,
+pub struct Cluster {
+    parts: Vec<Part>,
+    gears: Vec<Gear>,
+    rows: Range<usize>,
+    cols: Range<usize>,
+    sum: u32,
 }
 
-fn parse_gears((row, line): (usize, &str)) -> Vec<Gear> {
-    GEARS_REG
-        .captures_iter(line)
-        .map(|cap| Gear {
-            row,
-            col: cap.get(0).unwrap().start(),
+// Parts joined transitively through shared symbols, largest first. Parts
+// and symbols that touch nothing are not part of any assembly.
+fn clusters(input: &Schematic) -> Vec<Cluster> {
+    // parts are nodes 0..parts.len(), gears follow them
+    let offset = input.parts.len();
+    let mut union_find = UnionFind::new(offset + input.gears.len());
+    input
+        .part_gears
+        .iter()
+        .enumerate()
+        .for_each(|(part, gears)| {
+            gears
+                .iter()
+                .for_each(|gear| union_find.union(part, offset + gear))
+        });
+
+    let mut components: HashMap<usize, (Vec<usize>, Vec<usize>)> = HashMap::new();
+    (0..input.parts.len())
+        .filter(|&part| !input.part_gears[part].is_empty())
+        .for_each(|part| {
+            components
+                .entry(union_find.find(part))
+                .or_default()
+                .0
+                .push(part)
+        });
+    (0..input.gears.len())
+        .filter(|&gear| !input.gear_parts[gear].is_empty())
+        .for_each(|gear| {
+            components
+                .entry(union_find.find(offset + gear))
+                .or_default()
+                .1
+                .push(gear)
+        });
+
+    components
+        .into_values()
+        .map(|(parts, gears)| {
+            let parts = parts
+                .iter()
+                .map(|&part| input.parts[part].clone())
+                .collect_vec();
+            let gears = gears
+                .iter()
+                .map(|&gear| input.gears[gear].clone())
+                .collect_vec();
+            let cells = parts
+                .iter()
+                .flat_map(|part| [(part.row, part.pos.start), (part.row, part.pos.end - 1)])
+                .chain(gears.iter().map(|gear| (gear.row, gear.col)));
+            let (rows, cols): (Vec<usize>, Vec<usize>) = cells.unzip();
+            Cluster {
+                sum: parts.iter().map(|part| part.id).sum(),
+                rows: *rows.iter().min().unwrap()..*rows.iter().max().unwrap() + 1,
+                cols: *cols.iter().min().unwrap()..*cols.iter().max().unwrap() + 1,
+                parts,
+                gears,
+            }
+        })
+        .sorted_by(|a, b| {
+            b.parts
+                .len()
+                .cmp(&a.parts.len())
+                .then(b.sum.cmp(&a.sum))
+                .then(a.rows.start.cmp(&b.rows.start))
+                .then(a.cols.start.cmp(&b.cols.start))
         })
         .collect()
 }
 
+pub fn assembly(input: &Schematic) -> String {
+    let clusters = clusters(input);
+    match clusters.first() {
+        Some(largest) => format!(
+            "{} clusters, largest has {} parts {:?} and {} symbols, sum {}, rows {:?} cols {:?}",
+            clusters.len(),
+            largest.parts.len(),
+            largest.parts.iter().map(|part| part.id).collect_vec(),
+            largest.gears.len(),
+            largest.sum,
+            largest.rows,
+            largest.cols
+        ),
+        None => "no clusters".to_string(),
+    }
+}
+
+static GREEN: &str = "\x1b[32m";
+static RED: &str = "\x1b[31m";
+static HIGHLIGHT: &str = "\x1b[1;33m";
+static DIM: &str = "\x1b[2m";
+static RESET: &str = "\x1b[0m";
+
+struct RenderOptions {
+    legend: bool,
+    totals: bool,
+}
+
+fn paint(style: Option<&str>, text: &str) -> String {
+    match style {
+        Some(style) => format!("{style}{text}{RESET}"),
+        None => text.to_string(),
+    }
+}
+
+fn render_with(input: &Schematic, options: &RenderOptions) -> String {
+    let mut styles = input
+        .lines
+        .iter()
+        .map(|line| vec![None; line.len()])
+        .collect_vec();
+    let mut totals = vec![0; input.lines.len()];
+
+    input
+        .parts
+        .iter()
+        .zip(&input.part_gears)
+        .for_each(|(part, gears)| {
+            let style = if gears.is_empty() {
+                RED
+            } else {
+                totals[part.row] += part.id;
+                GREEN
+            };
+            part.pos
+                .clone()
+                .for_each(|col| styles[part.row][col] = Some(style));
+        });
+
+    let gear_ratio = Rule::gear_ratio();
+    input
+        .gears
+        .iter()
+        .zip(&input.gear_parts)
+        .for_each(|(gear, parts)| {
+            styles[gear.row][gear.col] = Some(if gear_ratio.matches(gear, parts.len()) {
+                HIGHLIGHT
+            } else {
+                DIM
+            });
+        });
+
+    let width = input.lines.iter().map(String::len).max().unwrap_or(0);
+    let mut rendered = input
+        .lines
+        .iter()
+        .zip(&styles)
+        .zip(&totals)
+        .map(|((line, styles), total)| {
+            let row = line
+                .chars()
+                .zip(styles)
+                .group_by(|(_, &style)| style)
+                .into_iter()
+                .map(|(style, run)| paint(style, &run.map(|(c, _)| c).collect::<String>()))
+                .collect::<String>();
+            if options.totals {
+                format!("{row}{} {total:>6}", " ".repeat(width - line.len()))
+            } else {
+                row
+            }
+        })
+        .join("\n");
+
+    if options.legend {
+        rendered.push_str(&format!(
+            "\n\n{} part  {} not a part  {} gear  {} symbol",
+            paint(Some(GREEN), "123"),
+            paint(Some(RED), "123"),
+            paint(Some(HIGHLIGHT), "*"),
+            paint(Some(DIM), "#")
+        ));
+    }
+    rendered
+}
+
+pub fn render(input: &Schematic) -> String {
+    render_with(
+        input,
+        &RenderOptions {
+            legend: true,
+            totals: true,
+        },
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use insta::{assert_debug_snapshot, assert_snapshot};
+
     use super::*;
 
     #[test]
@@ -186,11 +504,144 @@ mod tests {
 
     #[test]
     fn test_part_1() {
-        assert_eq!(part_1(&INPUT), 4361);
+        assert_eq!(part_1(&generator(INPUT)), 4361);
     }
 
     #[test]
     fn test_part_2() {
-        assert_eq!(part_2(&INPUT), 467835);
+        assert_eq!(part_2(&generator(INPUT)), Ok(467835));
+    }
+
+    #[test]
+    fn test_borders() {
+        // gears in every corner and along every edge
+        let input = generator(
+            "*2..3*
+4....5
+..*...
+6....7
+*8..9*",
+        );
+        assert_eq!(part_1(&input), 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9);
+        assert_eq!(part_2(&input), Ok(2 * 4 + 3 * 5 + 6 * 8 + 7 * 9));
+
+        // symbols in the last column and first row, next to parts on the other side
+        let input = generator(
+            "..#
+.12
+..-
+3..
+&..",
+        );
+        assert_eq!(part_1(&input), 12 + 3);
+        assert_eq!(part_2(&input), Ok(0));
+
+        // a single row and a single column
+        assert_eq!(part_1(&generator("1*2")), 3);
+        assert_eq!(part_2(&generator("1*2")), Ok(2));
+        assert_eq!(part_2(&generator("1\n*\n2")), Ok(2));
+        assert_eq!(part_1(&generator("1\n.\n2")), 0);
+    }
+
+    #[test]
+    fn test_adjacency() {
+        let input = generator(INPUT);
+        assert_eq!(input.gears[0].symbol, '*');
+        assert_eq!(input.gear_parts[0], vec![0, 2]);
+        assert!(input.part_gears[1].is_empty());
+        assert_eq!(input.part_gears[0], vec![0]);
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!("* ==2 product".parse(), Ok(Rule::gear_ratio()));
+        assert_eq!("* 2 product".parse(), Ok(Rule::gear_ratio()));
+        assert_eq!(
+            "#$ >=3 max".parse(),
+            Ok(Rule {
+                symbols: vec!['#', '$'],
+                count: Count::AtLeast(3),
+                aggregate: Aggregate::Max
+            })
+        );
+        assert!("any >=1".parse::<Rule>().is_err());
+        assert!("any >x sum".parse::<Rule>().is_err());
+        assert!("any 1 mean".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let input = generator(INPUT);
+        let eval = |rule: &str| evaluate(&input, &rule.parse().unwrap());
+        assert_eq!(eval("* ==2 product"), Ok(467835));
+        assert_eq!(eval("any >=1 sum"), Ok(part_1(&input) as u64));
+        assert_eq!(eval("* ==1 sum"), Ok(617));
+        assert_eq!(eval("*$ >=1 max"), Ok(467 + 617 + 755 + 664));
+        assert_eq!(eval("# >=2 sum"), Ok(0));
+    }
+
+    #[test]
+    fn test_evaluate_overflow() {
+        let input = generator("999.999\n...#...\n999.999");
+        let eval = |rule: &str| evaluate(&input, &rule.parse().unwrap());
+        assert_eq!(eval("# >=3 product"), Ok(999_u64.pow(4)));
+
+        let input = generator("99999.99999\n.....#.....\n99999.99999");
+        let eval = |rule: &str| evaluate(&input, &rule.parse().unwrap());
+        assert!(eval("any >=1 product").is_err());
+        assert_eq!(eval("any >=1 sum"), Ok(4 * 99999));
+    }
+
+    #[test]
+    fn test_render() {
+        let input = generator(INPUT);
+        let plain = render_with(
+            &input,
+            &RenderOptions {
+                legend: false,
+                totals: false,
+            },
+        );
+        assert_eq!(
+            plain
+                .replace(GREEN, "")
+                .replace(RED, "")
+                .replace(HIGHLIGHT, "")
+                .replace(DIM, "")
+                .replace(RESET, ""),
+            INPUT
+        );
+        assert_snapshot!(plain);
+        assert_snapshot!(render(&input));
+    }
+
+    #[test]
+    fn test_clusters() {
+        let input = generator(INPUT);
+        let found = clusters(&input);
+        assert_eq!(found.len(), 6);
+        assert_eq!(
+            found.iter().map(|cluster| cluster.sum).collect_vec(),
+            vec![755 + 598, 467 + 35, 664, 633, 617, 592]
+        );
+
+        let input = generator(
+            "1*2*3.
+......
+7#..9.
+.8..$.",
+        );
+        assert_debug_snapshot!(clusters(&input));
+        assert_eq!(
+            assembly(&input),
+            "3 clusters, largest has 3 parts [1, 2, 3] and 2 symbols, sum 6, rows 0..1 cols 0..5"
+        );
+        assert_eq!(assembly(&generator("1..\n..2")), "no clusters");
+
+        // one long chain of parts joined through symbols
+        let input = generator(&["1"; 100_000].join("*"));
+        let found = clusters(&input);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].parts.len(), 100_000);
     }
 }
@@ -1,57 +1,297 @@
-use std::collections::HashSet;
+use std::{collections::BTreeSet, str::FromStr};
 
 use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
 
-pub fn part_1(input: &str) -> u32 {
-    input.trim().lines().map(calc_card).sum()
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Card {
+    id: u32,
+    winning: BTreeSet<u32>,
+    mine: Vec<u32>,
 }
 
-fn parse_nums(input: &str) -> HashSet<u32> {
+lazy_static! {
+    static ref CARD_RE: Regex = Regex::new(r"^Card +(\d+): ([\d ]*)\|([\d ]*)$").unwrap();
+}
+
+fn parse_nums(input: &str) -> Result<Vec<u32>, String> {
     input
-        .trim()
-        .split(' ')
-        .filter_map(|num| {
-            if !num.is_empty() {
-                Some(num.parse::<u32>().unwrap())
-            } else {
-                None
-            }
+        .split_whitespace()
+        .map(|num| {
+            num.parse::<u32>()
+                .map_err(|err| format!("invalid number `{num}`: {err}"))
         })
         .collect()
 }
 
-fn card_wins(line: &str) -> u32 {
-    let split = line.split(&[':', '|']).collect_vec();
-    let winning = parse_nums(split[1]);
-    let mine = parse_nums(split[2]);
-    mine.iter().filter(|num| winning.contains(num)).count() as u32
+fn parse_card(line: &str) -> Result<Card, String> {
+    let cap = CARD_RE
+        .captures(line.trim())
+        .ok_or_else(|| format!("invalid card `{line}`"))?;
+    let id: u32 = cap[1]
+        .parse()
+        .map_err(|err| format!("invalid card id `{}`: {err}", &cap[1]))?;
+
+    let winning_nums = parse_nums(&cap[2])?;
+    let winning: BTreeSet<u32> = winning_nums.iter().copied().collect();
+    let mine = parse_nums(&cap[3])?;
+    if winning.len() != winning_nums.len() || !mine.iter().all_unique() {
+        return Err(format!("card {id} has duplicate numbers"));
+    }
+
+    Ok(Card { id, winning, mine })
+}
+
+pub fn generator(input: &str) -> Result<Vec<Card>, String> {
+    let cards: Vec<Card> = input.trim().lines().map(parse_card).try_collect()?;
+    if let Some((index, card)) = cards
+        .iter()
+        .enumerate()
+        .find(|(index, card)| card.id as usize != index + 1)
+    {
+        return Err(format!(
+            "expected card {}, found card {}",
+            index + 1,
+            card.id
+        ));
+    }
+    Ok(cards)
+}
+
+fn card_wins(card: &Card) -> u32 {
+    card.mine
+        .iter()
+        .filter(|num| card.winning.contains(num))
+        .count() as u32
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Score {
+    Linear,
+    Doubling,
+    Fibonacci,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Scoring {
+    score: Score,
+    cap: Option<u32>,
+}
+
+impl Scoring {
+    fn doubling() -> Self {
+        Scoring {
+            score: Score::Doubling,
+            cap: None,
+        }
+    }
+
+    fn points(&self, wins: u32) -> u32 {
+        let points = match (self.score, wins) {
+            (_, 0) => 0,
+            (Score::Linear, wins) => wins,
+            (Score::Doubling, wins) => 2_u32.saturating_pow(wins - 1),
+            (Score::Fibonacci, wins) => {
+                (1..wins)
+                    .fold((0_u32, 1_u32), |(a, b), _| (b, a.saturating_add(b)))
+                    .1
+            }
+        };
+        self.cap.map_or(points, |cap| points.min(cap))
+    }
+}
+
+// A card with `w` wins copies the `w` cards that start `offset` cards after
+// it, and the k-th of those receives `copies / decay^k` copies.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct CopyRule {
+    offset: usize,
+    decay: u32,
 }
 
-fn calc_card(line: &str) -> u32 {
-    let count_wins = card_wins(line);
-    if count_wins == 0 {
-        0
-    } else {
-        2_u32.pow((count_wins).saturating_sub(1))
+impl CopyRule {
+    fn next_cards() -> Self {
+        CopyRule {
+            offset: 0,
+            decay: 1,
+        }
     }
 }
 
-pub fn part_2(input: &str) -> u32 {
-    let cards = input.trim().lines().map(card_wins).collect_vec();
-    let len = cards.len();
-    let mut wins: Vec<u32> = vec![1; len];
-    for index in 0..len {
-        let card = cards[index] as usize;
-        for inc_index in 1..=card.min(len) {
-            wins[index + inc_index] += wins[index];
+#[derive(Debug, PartialEq, Eq)]
+struct Rules {
+    scoring: Scoring,
+    copies: CopyRule,
+}
+
+// Whitespace separated `key=value` pairs, e.g. `score=fibonacci cap=10 offset=1 decay=2`,
+// with anything left out taken from the puzzle rules.
+impl FromStr for Rules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules {
+            scoring: Scoring::doubling(),
+            copies: CopyRule::next_cards(),
+        };
+        for pair in s.split_whitespace() {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected `key=value`, got `{pair}`"))?;
+            let invalid = || format!("invalid {key} `{value}`");
+            match key {
+                "score" => {
+                    rules.scoring.score = match value {
+                        "linear" => Score::Linear,
+                        "doubling" => Score::Doubling,
+                        "fibonacci" => Score::Fibonacci,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "cap" => rules.scoring.cap = Some(value.parse().map_err(|_| invalid())?),
+                "offset" => rules.copies.offset = value.parse().map_err(|_| invalid())?,
+                "decay" => {
+                    rules.copies.decay = match value.parse() {
+                        Ok(decay) if decay > 0 => decay,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(format!("unknown rule `{key}`")),
+            }
         }
+        Ok(rules)
     }
-    wins.iter().sum()
+}
+
+fn calc_card(card: &Card, scoring: &Scoring) -> u32 {
+    scoring.points(card_wins(card))
+}
+
+pub fn part_1(input: &[Card]) -> u32 {
+    input
+        .iter()
+        .map(|card| calc_card(card, &Scoring::doubling()))
+        .sum()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Edge {
+    from: u32,
+    to: u32,
+    copies: u32,
+}
+
+#[derive(Debug)]
+struct Cascade {
+    // (card id, total copies including the original)
+    cards: Vec<(u32, u32)>,
+    edges: Vec<Edge>,
+}
+
+// Every card hands its copies on to the cards it wins, so an edge carries the
+// copies delivered by its source. Wins past the end of the table copy nothing.
+fn build_cascade(input: &[Card], rule: &CopyRule) -> Cascade {
+    let len = input.len();
+    let mut copies: Vec<u32> = vec![1; len];
+    let mut edges = vec![];
+    for (index, card) in input.iter().enumerate() {
+        let start = (index + 1).saturating_add(rule.offset);
+        let end = start.saturating_add(card_wins(card) as usize).min(len);
+        for (k, next) in (start..end).enumerate() {
+            let delivered = rule
+                .decay
+                .checked_pow(k as u32)
+                .map_or(0, |divisor| copies[index] / divisor);
+            if delivered == 0 {
+                break;
+            }
+            copies[next] += delivered;
+            edges.push(Edge {
+                from: card.id,
+                to: input[next].id,
+                copies: delivered,
+            });
+        }
+    }
+
+    Cascade {
+        cards: input.iter().map(|card| card.id).zip(copies).collect(),
+        edges,
+    }
+}
+
+fn total_copies(input: &[Card], rule: &CopyRule) -> u32 {
+    build_cascade(input, rule)
+        .cards
+        .iter()
+        .map(|(_, copies)| copies)
+        .sum()
+}
+
+pub fn part_2(input: &[Card]) -> u32 {
+    total_copies(input, &CopyRule::next_cards())
+}
+
+// The rules are read from `DAY4_RULES`, e.g. `DAY4_RULES="score=linear decay=2" cargo run -- -d 4`
+pub fn variant(input: &[Card]) -> String {
+    let Ok(rules) = std::env::var("DAY4_RULES") else {
+        return "set DAY4_RULES to play a variant".to_string();
+    };
+    match rules.parse::<Rules>() {
+        Ok(rules) => format!(
+            "{} points, {} cards",
+            input
+                .iter()
+                .map(|card| calc_card(card, &rules.scoring))
+                .sum::<u32>(),
+            total_copies(input, &rules.copies)
+        ),
+        Err(err) => format!("invalid rules: {err}"),
+    }
+}
+
+pub fn cascade_json(input: &[Card]) -> String {
+    let cascade = build_cascade(input, &CopyRule::next_cards());
+    let cards = cascade
+        .cards
+        .iter()
+        .map(|(id, copies)| format!("{{\"id\":{id},\"copies\":{copies}}}"))
+        .join(",");
+    let edges = cascade
+        .edges
+        .iter()
+        .map(|edge| {
+            format!(
+                "{{\"from\":{},\"to\":{},\"copies\":{}}}",
+                edge.from, edge.to, edge.copies
+            )
+        })
+        .join(",");
+    format!("{{\"cards\":[{cards}],\"edges\":[{edges}]}}")
+}
+
+pub fn cascade_dot(input: &[Card]) -> String {
+    let cascade = build_cascade(input, &CopyRule::next_cards());
+    let cards = cascade
+        .cards
+        .iter()
+        .map(|(id, copies)| format!("  {id} [label=\"Card {id}\\n{copies} copies\"];"));
+    let edges = cascade.edges.iter().map(|edge| {
+        format!(
+            "  {} -> {} [label=\"{}\", penwidth={:.1}];",
+            edge.from,
+            edge.to,
+            edge.copies,
+            1.0 + (edge.copies as f64).log2()
+        )
+    });
+    format!("digraph cascade {{\n{}\n}}", cards.chain(edges).join("\n"))
 }
 
 #[cfg(test)]
 mod tests {
-    use itertools::Itertools;
+    use insta::{assert_debug_snapshot, assert_snapshot};
 
     use super::*;
 
@@ -62,24 +302,171 @@ Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
 Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
 Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
 
+    #[test]
+    fn test_generator() {
+        assert_debug_snapshot!(generator(INPUT).unwrap()[0]);
+        assert_eq!(
+            generator("Card 1: 1 2 | 3\nCard 3: 1 | 2"),
+            Err("expected card 2, found card 3".to_string())
+        );
+        assert_eq!(
+            generator("Card 1: 1 2 1 | 3"),
+            Err("card 1 has duplicate numbers".to_string())
+        );
+        assert_eq!(
+            generator("Card 1: 1 2 | 3 3"),
+            Err("card 1 has duplicate numbers".to_string())
+        );
+        assert!(generator("Card 1: 1 2 3").is_err());
+        assert_eq!(
+            generator("Card 99999999999: 1 | 2"),
+            Err(
+                "invalid card id `99999999999`: number too large to fit in target type".to_string()
+            )
+        );
+    }
+
     #[test]
     fn test_check_line() {
-        let lines = INPUT.lines().collect_vec();
-        assert_eq!(calc_card(lines[0]), 8);
-        assert_eq!(calc_card(lines[1]), 2);
-        assert_eq!(calc_card(lines[2]), 2);
-        assert_eq!(calc_card(lines[3]), 1);
-        assert_eq!(calc_card(lines[4]), 0);
-        assert_eq!(calc_card(lines[5]), 0);
+        let cards = generator(INPUT).unwrap();
+        let doubling = Scoring::doubling();
+        assert_eq!(calc_card(&cards[0], &doubling), 8);
+        assert_eq!(calc_card(&cards[1], &doubling), 2);
+        assert_eq!(calc_card(&cards[2], &doubling), 2);
+        assert_eq!(calc_card(&cards[3], &doubling), 1);
+        assert_eq!(calc_card(&cards[4], &doubling), 0);
+        assert_eq!(calc_card(&cards[5], &doubling), 0);
     }
 
     #[test]
     fn test_part_1() {
-        assert_eq!(part_1(&INPUT), 13);
+        assert_eq!(part_1(&generator(INPUT).unwrap()), 13);
     }
 
     #[test]
     fn test_part_2() {
-        assert_eq!(part_2(&INPUT), 30);
+        assert_eq!(part_2(&generator(INPUT).unwrap()), 30);
+    }
+
+    #[test]
+    fn test_part_2_overflow() {
+        // the last cards win more copies than there are cards left
+        let input = "Card 1: 1 | 2
+Card 2: 1 2 3 | 1 2 3
+Card 3: 1 2 3 4 | 1 2 3 4";
+        assert_eq!(part_2(&generator(input).unwrap()), 1 + 1 + 2);
+
+        let input = "Card 1: 5 6 7 | 5 6 7";
+        assert_eq!(part_2(&generator(input).unwrap()), 1);
+    }
+
+    #[test]
+    fn test_build_cascade() {
+        let cascade = build_cascade(&generator(INPUT).unwrap(), &CopyRule::next_cards());
+        assert_eq!(
+            cascade.cards,
+            vec![(1, 1), (2, 2), (3, 4), (4, 8), (5, 14), (6, 1)]
+        );
+        assert_eq!(cascade.edges.len(), 4 + 2 + 2 + 1);
+        assert_eq!(
+            cascade
+                .edges
+                .iter()
+                .filter(|edge| edge.to == 5)
+                .collect_vec(),
+            vec![
+                &Edge {
+                    from: 1,
+                    to: 5,
+                    copies: 1
+                },
+                &Edge {
+                    from: 3,
+                    to: 5,
+                    copies: 4
+                },
+                &Edge {
+                    from: 4,
+                    to: 5,
+                    copies: 8
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cascade_export() {
+        let input = generator("Card 1: 1 2 | 1 2\nCard 2: 1 | 1\nCard 3: 1 | 2").unwrap();
+        assert_eq!(
+            cascade_json(&input),
+            r#"{"cards":[{"id":1,"copies":1},{"id":2,"copies":2},{"id":3,"copies":4}],"edges":[{"from":1,"to":2,"copies":1},{"from":1,"to":3,"copies":1},{"from":2,"to":3,"copies":2}]}"#
+        );
+        assert_snapshot!(cascade_dot(&input));
+    }
+
+    #[test]
+    fn test_scoring() {
+        let points = |rules: &str, wins: u32| rules.parse::<Rules>().unwrap().scoring.points(wins);
+        assert_eq!(
+            (0..7).map(|wins| points("", wins)).collect_vec(),
+            vec![0, 1, 2, 4, 8, 16, 32]
+        );
+        assert_eq!(
+            (0..7)
+                .map(|wins| points("score=linear", wins))
+                .collect_vec(),
+            vec![0, 1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(
+            (0..7)
+                .map(|wins| points("score=fibonacci", wins))
+                .collect_vec(),
+            vec![0, 1, 1, 2, 3, 5, 8]
+        );
+        assert_eq!(
+            (0..7).map(|wins| points("cap=10", wins)).collect_vec(),
+            vec![0, 1, 2, 4, 8, 10, 10]
+        );
+        assert_eq!(points("", 40), u32::MAX);
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(
+            "".parse(),
+            Ok(Rules {
+                scoring: Scoring::doubling(),
+                copies: CopyRule::next_cards()
+            })
+        );
+        assert_eq!(
+            "score=fibonacci cap=10 offset=1 decay=2".parse(),
+            Ok(Rules {
+                scoring: Scoring {
+                    score: Score::Fibonacci,
+                    cap: Some(10)
+                },
+                copies: CopyRule {
+                    offset: 1,
+                    decay: 2
+                }
+            })
+        );
+        assert!("decay=0".parse::<Rules>().is_err());
+        assert!("score=triple".parse::<Rules>().is_err());
+        assert!("offset".parse::<Rules>().is_err());
+        assert!("colour=red".parse::<Rules>().is_err());
+    }
+
+    #[test]
+    fn test_copy_rules() {
+        let input = generator(INPUT).unwrap();
+        let copies = |offset, decay| total_copies(&input, &CopyRule { offset, decay });
+        assert_eq!(copies(0, 1), 30);
+        // card 1 skips card 2 and copies 3, 4, 5, 6, card 2 copies 4 and 5, ...
+        assert_eq!(copies(1, 1), 1 + 1 + 2 + 3 + 5 + 7);
+        // card 1 sends 1 copy to card 2 and none further, card 2 sends 2 to card 3 and 1 to card 4, ...
+        assert_eq!(copies(0, 2), 1 + 2 + 3 + 5 + 7 + 1);
+        assert_eq!(copies(usize::MAX, 1), 6);
     }
 }
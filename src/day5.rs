@@ -1,8 +1,7 @@
 use itertools::Itertools;
 use lazy_static::lazy_static;
-use rayon::prelude::*;
 use regex::Regex;
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, ops::Range, slice};
 
 type Seed = u64;
 type Mapping = String;
@@ -15,19 +14,33 @@ struct Map {
     range_length: u64,
 }
 
-type Maps = HashMap<Mapping, Vec<Map>>;
+impl Map {
+    // Clamped so neither end runs past `u64::MAX`, see `Lint::Overflow`
+    fn source(&self) -> Range<u64> {
+        let len = self
+            .range_length
+            .min(u64::MAX - self.source_start)
+            .min(u64::MAX - self.destination_start);
+        self.source_start..self.source_start + len
+    }
+}
+
+// keyed by (from, to), so sections leaving the same category stay apart
+type Maps = HashMap<(Mapping, Mapping), Vec<Map>>;
 
 #[derive(Debug)]
 pub struct Almanac {
     seeds: Vec<Seed>,
     maps: Maps,
+    // every `from-to-to` section header, in input order
+    links: Vec<(Mapping, Mapping)>,
 }
 
 lazy_static! {
     static ref MAPPING_RE: Regex = Regex::new(r"(\w+)-to-(\w+) map:").unwrap();
 }
 
-pub fn generator(input: &str) -> Almanac {
+fn parse_almanac(input: &str) -> Almanac {
     let seeds = input.lines().next().unwrap();
     let seeds = seeds
         .split(' ')
@@ -35,98 +48,592 @@ pub fn generator(input: &str) -> Almanac {
         .map(|num| num.parse::<Seed>().unwrap())
         .collect_vec();
 
-    let maps: Maps = input
-        .split("\n\n")
-        .skip(1)
-        .map(|section| {
-            let mapping = MAPPING_RE.captures(section).unwrap();
-            (
-                mapping[1].to_string(),
-                section
-                    .lines()
-                    .skip(1)
-                    .map(|line| {
-                        let nums = line
-                            .split(' ')
-                            .map(|num| num.parse::<u64>().unwrap())
-                            .collect_vec();
-                        Map {
-                            to: mapping[2].to_string(),
-                            destination_start: nums[0],
-                            source_start: nums[1],
-                            range_length: nums[2],
-                        }
-                    })
-                    .collect_vec(),
-            )
-        })
-        .collect();
+    let mut links = vec![];
+    let mut maps = Maps::new();
+    input.split("\n\n").skip(1).for_each(|section| {
+        let mapping = MAPPING_RE.captures(section).unwrap();
+        let link = (mapping[1].to_string(), mapping[2].to_string());
+        links.push(link.clone());
+        // a repeated header continues its section
+        maps.entry(link)
+            .or_default()
+            .extend(section.lines().skip(1).map(|line| {
+                let nums = line
+                    .split(' ')
+                    .map(|num| num.parse::<u64>().unwrap())
+                    .collect_vec();
+                Map {
+                    to: mapping[2].to_string(),
+                    destination_start: nums[0],
+                    source_start: nums[1],
+                    range_length: nums[2],
+                }
+            }));
+    });
 
-    Almanac { seeds, maps }
+    Almanac { seeds, maps, links }
 }
 
-fn seed_location(seed: Seed, almanac: &Almanac) -> u64 {
-    let location = "location".to_string();
-    let mut cur_map = "seed".to_string();
-    let mut cur_index = seed;
-    let mut count = 0;
+#[derive(Debug, PartialEq, Eq)]
+enum GraphError {
+    // a category on the way that has no map out of it
+    Missing(Mapping),
+    Cycle(Vec<Mapping>),
+    Branch(Mapping, Vec<Mapping>),
+}
 
-    // println!("0 - soil {cur_index}");
-    while cur_map.ne(&location) && count < 100 {
-        let found_maps = almanac.maps.get(&cur_map).unwrap();
-        let found_map = found_maps.iter().find(|map| {
-            cur_index >= map.source_start && cur_index < map.source_start + map.range_length
-        });
-        cur_index = if let Some(map) = found_map {
-            let offset = cur_index - map.source_start;
-            map.destination_start + offset
-        } else {
-            cur_index
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::Missing(category) => write!(f, "no map from {category}"),
+            GraphError::Cycle(categories) => {
+                write!(f, "cycle {} -> {}", categories.join(" -> "), categories[0])
+            }
+            GraphError::Branch(category, to) => {
+                write!(
+                    f,
+                    "{category} maps to more than one category: {}",
+                    to.join(", ")
+                )
+            }
+        }
+    }
+}
+
+fn next_categories<'a>(almanac: &'a Almanac, category: &str) -> Vec<&'a Mapping> {
+    almanac
+        .links
+        .iter()
+        .filter(|(from, _)| from == category)
+        .map(|(_, to)| to)
+        .collect()
+}
+
+// The categories visited going from one category to another
+fn path(almanac: &Almanac, from: &str, to: &str) -> Result<Vec<Mapping>, GraphError> {
+    let mut path = vec![from.to_string()];
+
+    while path.last().unwrap() != to {
+        let cur_map = path.last().unwrap();
+        let next = match next_categories(almanac, cur_map)[..] {
+            [] => return Err(GraphError::Missing(cur_map.clone())),
+            [next] => next,
+            ref nexts => {
+                return Err(GraphError::Branch(
+                    cur_map.clone(),
+                    nexts.iter().map(|&next| next.clone()).collect(),
+                ))
+            }
         };
+        if let Some(index) = path.iter().position(|category| category == next) {
+            return Err(GraphError::Cycle(path.split_off(index)));
+        }
+        path.push(next.clone());
+    }
 
-        cur_map = found_maps[0].to.clone();
-        count += 1;
+    Ok(path)
+}
 
-        // println!("{count} - {} {cur_index}", &cur_map);
+// The layers to walk from one category to another
+fn chain<'a>(almanac: &'a Almanac, from: &str, to: &str) -> Result<Vec<&'a [Map]>, GraphError> {
+    Ok(path(almanac, from, to)?
+        .into_iter()
+        .tuple_windows()
+        .map(|link| almanac.maps.get(&link).map_or(&[][..], |maps| &maps[..]))
+        .collect())
+}
+
+// Every branch and cycle in the almanac, and whatever stops seeds reaching locations
+fn validate(almanac: &Almanac) -> Vec<GraphError> {
+    let categories = almanac
+        .links
+        .iter()
+        .flat_map(|(from, to)| [from, to])
+        .unique()
+        .sorted()
+        .collect_vec();
+
+    let branches = categories.iter().filter_map(|&category| {
+        let nexts = next_categories(almanac, category);
+        (nexts.len() > 1)
+            .then(|| GraphError::Branch(category.clone(), nexts.into_iter().cloned().collect()))
+    });
+    let cycles = categories
+        .iter()
+        .filter_map(|category| match path(almanac, category, "location") {
+            Err(GraphError::Cycle(mut cycle)) => {
+                // start every cycle at its smallest category so each is reported once
+                let start = cycle.iter().position_min().unwrap();
+                cycle.rotate_left(start);
+                Some(GraphError::Cycle(cycle))
+            }
+            _ => None,
+        })
+        .unique_by(|cycle| cycle.to_string());
+    let missing = match path(almanac, "seed", "location") {
+        Err(err @ GraphError::Missing(_)) => Some(err),
+        _ => None,
+    };
+
+    branches.chain(cycles).chain(missing).collect()
+}
+
+// The solvers walk from seeds to locations, so an almanac that cannot is
+// rejected here with the same issues `graph` would list.
+pub fn generator(input: &str) -> Result<Almanac, String> {
+    let almanac = parse_almanac(input);
+    match path(&almanac, "seed", "location") {
+        Ok(_) => Ok(almanac),
+        Err(_) => Err(validate(&almanac).iter().join("\n")),
     }
+}
+
+pub fn graph(almanac: &Almanac) -> String {
+    let issues = validate(almanac);
+    if issues.is_empty() {
+        path(almanac, "seed", "location")
+            .expect("generator checks seeds reach locations")
+            .join(" -> ")
+    } else {
+        issues.iter().join("\n")
+    }
+}
+
+// `source` maps onto the values starting at `destination`
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Piece {
+    source: Range<u64>,
+    destination: u64,
+}
+
+impl Piece {
+    fn image(&self) -> Range<u64> {
+        self.destination..self.destination + (self.source.end - self.source.start)
+    }
+}
+
+// Splits `range` wherever it crosses a map boundary. Like `find`, the first
+// map covering a value wins, and values no map covers keep their number.
+fn split(range: Range<u64>, maps: &[Map]) -> Vec<Piece> {
+    let mut pending = vec![range];
+    let mut pieces = vec![];
+    for map in maps {
+        let source = map.source();
+        let mut unmapped = vec![];
+        for range in pending {
+            let start = range.start.max(source.start);
+            let end = range.end.min(source.end);
+            if start < end {
+                pieces.push(Piece {
+                    source: start..end,
+                    destination: start - source.start + map.destination_start,
+                });
+                if range.start < start {
+                    unmapped.push(range.start..start);
+                }
+                if end < range.end {
+                    unmapped.push(end..range.end);
+                }
+            } else {
+                unmapped.push(range);
+            }
+        }
+        pending = unmapped;
+    }
+    pieces.extend(pending.into_iter().map(|range| Piece {
+        destination: range.start,
+        source: range,
+    }));
+    pieces
+}
+
+// Pushes whole ranges through one layer
+fn map_ranges(ranges: Vec<Range<u64>>, maps: &[Map]) -> Vec<Range<u64>> {
+    ranges
+        .into_iter()
+        .flat_map(|range| split(range, maps))
+        .map(|piece| piece.image())
+        .collect()
+}
 
-    cur_index
+fn location_ranges(ranges: Vec<Range<u64>>, almanac: &Almanac) -> Vec<Range<u64>> {
+    chain(almanac, "seed", "location")
+        .expect("generator checks seeds reach locations")
+        .into_iter()
+        .fold(ranges, map_ranges)
+}
+
+// Sorts pieces and joins neighbours that continue the same offset
+fn merge_pieces(pieces: Vec<Piece>) -> Vec<Piece> {
+    pieces
+        .into_iter()
+        .sorted_by_key(|piece| piece.source.start)
+        .coalesce(|prev, next| {
+            if prev.source.end == next.source.start && prev.image().end == next.destination {
+                Ok(Piece {
+                    source: prev.source.start..next.source.end,
+                    destination: prev.destination,
+                })
+            } else {
+                Err((prev, next))
+            }
+        })
+        .collect()
+}
+
+// A chain of layers collapsed into one function, as sorted pieces covering
+// every value below `u64::MAX`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Composed {
+    from: String,
+    to: String,
+    pieces: Vec<Piece>,
+}
+
+impl Composed {
+    fn get(&self, value: u64) -> u64 {
+        let index = self
+            .pieces
+            .partition_point(|piece| piece.source.end <= value);
+        match self.pieces.get(index) {
+            Some(piece) => value - piece.source.start + piece.destination,
+            None => value,
+        }
+    }
+}
+
+fn compose(almanac: &Almanac, from: &str, to: &str) -> Result<Composed, GraphError> {
+    let identity = vec![Piece {
+        source: 0..u64::MAX,
+        destination: 0,
+    }];
+    let pieces = chain(almanac, from, to)?
+        .into_iter()
+        .fold(identity, |pieces, maps| {
+            pieces
+                .iter()
+                .flat_map(|piece| {
+                    split(piece.image(), maps).into_iter().map(|next| Piece {
+                        source: next.source.start - piece.destination + piece.source.start
+                            ..next.source.end - piece.destination + piece.source.start,
+                        destination: next.destination,
+                    })
+                })
+                .collect()
+        })
+        .into_iter()
+        .collect_vec();
+
+    Ok(Composed {
+        from: from.to_string(),
+        to: to.to_string(),
+        pieces: merge_pieces(pieces),
+    })
+}
+
+// Only the pieces that move their values are listed, as in the puzzle input.
+impl fmt::Display for Composed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-to-{} map:", self.from, self.to)?;
+        for piece in &self.pieces {
+            if piece.source.start != piece.destination {
+                write!(
+                    f,
+                    "\n{} {} {}",
+                    piece.destination,
+                    piece.source.start,
+                    piece.source.end - piece.source.start
+                )?;
+            }
+        }
+        Ok(())
+    }
 }
 
 pub fn part_1(almanac: &Almanac) -> Seed {
+    let composed =
+        compose(almanac, "seed", "location").expect("generator checks seeds reach locations");
     almanac
         .seeds
         .iter()
-        .map(|seed| seed_location(*seed, almanac))
+        .map(|&seed| composed.get(seed))
         .min()
         .unwrap()
 }
 
-pub fn part_2(almanac: &Almanac) -> u64 {
+pub fn composed(almanac: &Almanac) -> String {
+    compose(almanac, "seed", "location")
+        .expect("generator checks seeds reach locations")
+        .to_string()
+}
+
+// A trailing seed without a length is not a range, and ranges stop at `u64::MAX`
+fn seed_ranges(almanac: &Almanac) -> Vec<Range<u64>> {
     almanac
         .seeds
-        .chunks(2)
-        .map(|range| {
-            let range = range[0]..(range[0] + range[1]);
-            println!("checking range {:?}", &range);
-            range
-                .into_par_iter()
-                .map(|seed| seed_location(seed, almanac))
-                .min()
-                .unwrap()
-        })
+        .chunks_exact(2)
+        .map(|range| range[0]..range[0].saturating_add(range[1]))
+        .collect()
+}
+
+pub fn part_2(almanac: &Almanac) -> u64 {
+    location_ranges(seed_ranges(almanac), almanac)
+        .iter()
+        .map(|range| range.start)
         .min()
         .unwrap()
 }
 
+// Sorts and merges overlapping or touching ranges
+fn merge_ranges(ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges
+        .into_iter()
+        .filter(|range| !range.is_empty())
+        .sorted_by_key(|range| range.start)
+        .coalesce(|prev, next| {
+            if next.start <= prev.end {
+                Ok(prev.start..prev.end.max(next.end))
+            } else {
+                Err((prev, next))
+            }
+        })
+        .collect()
+}
+
+fn intersect_ranges(a: &[Range<u64>], b: &[Range<u64>]) -> Vec<Range<u64>> {
+    merge_ranges(
+        a.iter()
+            .cartesian_product(b)
+            .map(|(a, b)| a.start.max(b.start)..a.end.min(b.end))
+            .collect(),
+    )
+}
+
+// Every value of one layer that lands in `ranges`
+fn preimage(ranges: &[Range<u64>], maps: &[Map]) -> Vec<Range<u64>> {
+    merge_ranges(
+        split(0..u64::MAX, maps)
+            .iter()
+            .cartesian_product(ranges)
+            .filter_map(|(piece, range)| {
+                let image = piece.image();
+                let start = image.start.max(range.start);
+                let end = image.end.min(range.end);
+                (start < end).then(|| {
+                    start - piece.destination + piece.source.start
+                        ..end - piece.destination + piece.source.start
+                })
+            })
+            .collect(),
+    )
+}
+
+// Seeds that end up in `locations`, as merged ranges, optionally limited to `seeds`
+fn seeds_reaching(
+    almanac: &Almanac,
+    locations: Range<u64>,
+    seeds: Option<&[Range<u64>]>,
+) -> Vec<Range<u64>> {
+    let preimage = chain(almanac, "seed", "location")
+        .expect("generator checks seeds reach locations")
+        .into_iter()
+        .rev()
+        .fold(merge_ranges(vec![locations]), |ranges, maps| {
+            preimage(&ranges, maps)
+        });
+    match seeds {
+        Some(seeds) => intersect_ranges(&preimage, seeds),
+        None => preimage,
+    }
+}
+
+fn seed_values(almanac: &Almanac) -> Vec<Range<u64>> {
+    almanac.seeds.iter().map(|&seed| seed..seed + 1).collect()
+}
+
+// The lowest location any of `seeds` reaches
+fn lowest_location(almanac: &Almanac, seeds: &[Range<u64>]) -> Option<u64> {
+    compose(almanac, "seed", "location")
+        .expect("generator checks seeds reach locations")
+        .pieces
+        .iter()
+        .filter_map(|piece| {
+            intersect_ranges(slice::from_ref(&piece.source), seeds)
+                .first()
+                .map(|range| range.start - piece.source.start + piece.destination)
+        })
+        .min()
+}
+
+pub fn inverse(almanac: &Almanac) -> String {
+    let lowest = |name, seeds: &[Range<u64>]| match lowest_location(almanac, seeds) {
+        Some(location) => format!(
+            "{name}: lowest location {location} from {:?}",
+            seeds_reaching(almanac, location..location + 1, Some(seeds))
+        ),
+        None => format!("{name}: no seeds"),
+    };
+    let ranges = if almanac.seeds.len().is_multiple_of(2) {
+        lowest("seed ranges", &seed_ranges(almanac))
+    } else {
+        "seed ranges: unavailable for an odd number of seeds".to_string()
+    };
+    format!("{}\n{ranges}", lowest("seeds", &seed_values(almanac)))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Lint {
+    // map lines are numbered from 1 within their section
+    Overlap(Mapping, usize, usize),
+    Gap(Mapping, Range<u64>),
+    Empty(Mapping, usize),
+    Overflow(Mapping, usize),
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::Overlap(section, a, b) => {
+                write!(f, "{section}: line {b} overlaps line {a}, which wins")
+            }
+            Lint::Gap(section, range) => write!(f, "{section}: nothing maps {range:?}"),
+            Lint::Empty(section, line) => write!(f, "{section}: line {line} has no length"),
+            Lint::Overflow(section, line) => write!(f, "{section}: line {line} runs past u64::MAX"),
+        }
+    }
+}
+
+fn sections(almanac: &Almanac) -> impl Iterator<Item = (String, &[Map])> {
+    almanac.links.iter().unique().map(|link @ (from, to)| {
+        (
+            format!("{from}-to-{to}"),
+            almanac.maps.get(link).map_or(&[][..], |maps| &maps[..]),
+        )
+    })
+}
+
+fn lint(almanac: &Almanac) -> Vec<Lint> {
+    sections(almanac)
+        .flat_map(|(section, maps)| {
+            let lines = maps.iter().enumerate().map(|(index, map)| (index + 1, map));
+            let sizes = lines.clone().filter_map(|(line, map)| {
+                if map.range_length == 0 {
+                    Some(Lint::Empty(section.clone(), line))
+                } else if map.source().end - map.source_start < map.range_length {
+                    Some(Lint::Overflow(section.clone(), line))
+                } else {
+                    None
+                }
+            });
+            let overlaps = lines
+                .clone()
+                .tuple_combinations()
+                .filter(|((_, a), (_, b))| {
+                    let (a, b) = (a.source(), b.source());
+                    a.start.max(b.start) < a.end.min(b.end)
+                })
+                .map(|((a, _), (b, _))| Lint::Overlap(section.clone(), a, b));
+            let gaps = merge_ranges(maps.iter().map(Map::source).collect())
+                .windows(2)
+                .map(|pair| Lint::Gap(section.clone(), pair[0].end..pair[1].start))
+                .collect_vec();
+            sizes.chain(overlaps).chain(gaps).collect_vec()
+        })
+        .collect()
+}
+
+// Rewrites every section as the fewest lines with the same effect: no overlaps,
+// no identity or empty lines, and neighbouring lines with the same offset joined.
+fn simplify(almanac: &Almanac) -> Almanac {
+    let maps = almanac
+        .links
+        .iter()
+        .unique()
+        .map(|link @ (_, to)| {
+            let maps = almanac.maps.get(link).map_or(&[][..], |maps| &maps[..]);
+            let lines = merge_pieces(split(0..u64::MAX, maps))
+                .into_iter()
+                .filter(|piece| piece.source.start != piece.destination)
+                .map(|piece| Map {
+                    to: to.clone(),
+                    destination_start: piece.destination,
+                    source_start: piece.source.start,
+                    range_length: piece.source.end - piece.source.start,
+                })
+                .collect();
+            (link.clone(), lines)
+        })
+        .collect();
+
+    Almanac {
+        seeds: almanac.seeds.clone(),
+        maps,
+        links: almanac.links.clone(),
+    }
+}
+
+impl fmt::Display for Almanac {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "seeds: {}", self.seeds.iter().join(" "))?;
+        for (section, maps) in sections(self) {
+            write!(f, "\n\n{section} map:")?;
+            for map in maps {
+                write!(
+                    f,
+                    "\n{} {} {}",
+                    map.destination_start, map.source_start, map.range_length
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn linted(almanac: &Almanac) -> String {
+    let lints = lint(almanac);
+    format!(
+        "{} lints\n{}\n\n{}",
+        lints.len(),
+        lints.iter().join("\n"),
+        simplify(almanac)
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use insta::assert_debug_snapshot;
+    use insta::{assert_debug_snapshot, assert_snapshot};
 
     use super::*;
+    use crate::test_util::Lcg;
 
     lazy_static! {
-        static ref ALMANAC: Almanac = generator(&INPUT);
+        static ref ALMANAC: Almanac = generator(INPUT).unwrap();
+    }
+
+    // walks the layers one value at a time
+    fn seed_location(seed: Seed, almanac: &Almanac) -> u64 {
+        let mut cur_map = "seed";
+        let mut cur_index = seed;
+
+        while cur_map != "location" {
+            let (_, found_maps) = almanac
+                .maps
+                .iter()
+                .find(|((from, _), _)| from == cur_map)
+                .unwrap();
+            let found_map = found_maps.iter().find(|map| {
+                cur_index >= map.source_start && cur_index < map.source_start + map.range_length
+            });
+            cur_index = if let Some(map) = found_map {
+                let offset = cur_index - map.source_start;
+                map.destination_start + offset
+            } else {
+                cur_index
+            };
+            cur_map = &found_maps[0].to;
+        }
+
+        cur_index
     }
 
     #[test]
@@ -154,6 +661,292 @@ mod tests {
         assert_eq!(part_2(&ALMANAC), 46);
     }
 
+    // brute force every seed in the seed ranges
+    fn brute_locations(almanac: &Almanac) -> Vec<u64> {
+        seed_ranges(almanac)
+            .into_iter()
+            .flatten()
+            .map(|seed| seed_location(seed, almanac))
+            .sorted()
+            .collect()
+    }
+
+    fn range_locations(almanac: &Almanac) -> Vec<u64> {
+        location_ranges(seed_ranges(almanac), almanac)
+            .into_iter()
+            .flatten()
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn test_location_ranges() {
+        assert_eq!(brute_locations(&ALMANAC), range_locations(&ALMANAC));
+
+        // small pseudo-random almanacs, including overlapping and out of order maps
+        let mut rng = Lcg::new(0x2545_f491);
+        let mut next = |max: u64| rng.below(max);
+        let categories = ["seed", "soil", "water", "location"];
+        for _ in 0..50 {
+            let seeds = (0..4)
+                .map(|i| if i % 2 == 0 { next(60) } else { next(20) })
+                .collect();
+            let maps = categories
+                .windows(2)
+                .map(|pair| {
+                    let maps = (0..1 + next(4))
+                        .map(|_| Map {
+                            to: pair[1].to_string(),
+                            destination_start: next(80),
+                            source_start: next(80),
+                            range_length: 1 + next(30),
+                        })
+                        .collect();
+                    ((pair[0].to_string(), pair[1].to_string()), maps)
+                })
+                .collect();
+            let links = categories
+                .windows(2)
+                .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+                .collect();
+            let almanac = Almanac { seeds, maps, links };
+            assert_eq!(brute_locations(&almanac), range_locations(&almanac));
+        }
+    }
+
+    #[test]
+    fn test_compose() {
+        let composed = compose(&ALMANAC, "seed", "location").unwrap();
+        (0..120).for_each(|seed| assert_eq!(composed.get(seed), seed_location(seed, &ALMANAC)));
+        assert_eq!(composed.get(u64::MAX - 1), u64::MAX - 1);
+        assert_eq!(composed.get(u64::MAX), u64::MAX);
+        assert!(composed
+            .pieces
+            .windows(2)
+            .all(|pair| pair[0].source.end == pair[1].source.start));
+        assert_snapshot!(composed.to_string());
+
+        let composed = compose(&ALMANAC, "soil", "water").unwrap();
+        assert_eq!(composed.get(81), 81);
+        assert_eq!(composed.get(53), 27);
+        assert_eq!(composed.get(14), 49);
+        assert_eq!(
+            compose(&ALMANAC, "seed", "seed").unwrap().pieces,
+            vec![Piece {
+                source: 0..u64::MAX,
+                destination: 0
+            }]
+        );
+        assert!(compose(&ALMANAC, "location", "seed").is_err());
+        assert!(compose(&ALMANAC, "water", "soil").is_err());
+    }
+
+    #[test]
+    fn test_seeds_reaching() {
+        let seeds = seed_ranges(&ALMANAC);
+        for locations in [0..1, 46..47, 40..60, 0..100, 90..1000] {
+            let brute = seeds
+                .iter()
+                .cloned()
+                .flatten()
+                .filter(|&seed| locations.contains(&seed_location(seed, &ALMANAC)))
+                .map(|seed| seed..seed + 1)
+                .collect();
+            assert_eq!(
+                seeds_reaching(&ALMANAC, locations, Some(&seeds)),
+                merge_ranges(brute)
+            );
+        }
+
+        assert_eq!(
+            seeds_reaching(&ALMANAC, 35..36, Some(&seed_values(&ALMANAC))),
+            vec![13..14]
+        );
+        // without intersecting, every seed number that reaches location 0
+        assert_eq!(seeds_reaching(&ALMANAC, 0..1, None), vec![70..71]);
+    }
+
+    #[test]
+    fn test_lowest_location() {
+        assert_eq!(lowest_location(&ALMANAC, &seed_values(&ALMANAC)), Some(35));
+        assert_eq!(lowest_location(&ALMANAC, &seed_ranges(&ALMANAC)), Some(46));
+        assert_eq!(lowest_location(&ALMANAC, &[]), None);
+        assert_eq!(
+            inverse(&ALMANAC),
+            "seeds: lowest location 35 from [13..14]
+seed ranges: lowest location 46 from [82..83]"
+        );
+
+        let mut almanac = generator(INPUT).unwrap();
+        almanac.seeds = vec![79, 14, 55];
+        assert_eq!(part_1(&almanac), 43);
+        assert_eq!(
+            inverse(&almanac),
+            "seeds: lowest location 43 from [14..15]
+seed ranges: unavailable for an odd number of seeds"
+        );
+        almanac.seeds = vec![u64::MAX - 1, u64::MAX];
+        assert_eq!(seed_ranges(&almanac), vec![u64::MAX - 1..u64::MAX]);
+    }
+
+    #[test]
+    fn test_path() {
+        assert_eq!(
+            graph(&ALMANAC),
+            "seed -> soil -> fertilizer -> water -> light -> temperature -> humidity -> location"
+        );
+        assert_eq!(
+            path(&ALMANAC, "water", "temperature"),
+            Ok(vec![
+                "water".to_string(),
+                "light".to_string(),
+                "temperature".to_string()
+            ])
+        );
+        assert_eq!(
+            path(&ALMANAC, "location", "seed"),
+            Err(GraphError::Missing("location".to_string()))
+        );
+        assert_eq!(
+            path(&ALMANAC, "water", "soil"),
+            Err(GraphError::Missing("location".to_string()))
+        );
+        assert_eq!(
+            path(&ALMANAC, "sand", "soil"),
+            Err(GraphError::Missing("sand".to_string()))
+        );
+        assert!(validate(&ALMANAC).is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let almanac = parse_almanac(
+            "seeds: 1 2
+
+seed-to-soil map:
+1 2 3
+
+soil-to-water map:
+
+soil-to-fertilizer map:
+5 5 5
+
+water-to-light map:
+
+light-to-water map:
+
+fertilizer-to-sand map:
+",
+        );
+        assert_eq!(
+            graph(&almanac),
+            "soil maps to more than one category: water, fertilizer
+cycle light -> water -> light"
+        );
+        assert_eq!(
+            path(&almanac, "fertilizer", "sand"),
+            Ok(vec!["fertilizer".to_string(), "sand".to_string()])
+        );
+        assert_eq!(compose(&almanac, "fertilizer", "sand").unwrap().get(7), 7);
+        assert_eq!(
+            path(&almanac, "water", "seed"),
+            Err(GraphError::Cycle(vec![
+                "water".to_string(),
+                "light".to_string()
+            ]))
+        );
+
+        let input = "seeds: 1 2\n\nseed-to-soil map:\n1 2 3\n";
+        assert_eq!(graph(&parse_almanac(input)), "no map from soil");
+        assert_eq!(generator(input).unwrap_err(), "no map from soil");
+    }
+
+    #[test]
+    fn test_lint() {
+        assert!(lint(&ALMANAC).is_empty());
+
+        let almanac = parse_almanac(
+            "seeds: 1 2
+
+seed-to-soil map:
+10 0 5
+20 3 5
+0 20 0
+1 30 2
+18446744073709551610 40 10
+",
+        );
+        assert_eq!(
+            lint(&almanac),
+            vec![
+                Lint::Empty("seed-to-soil".to_string(), 3),
+                Lint::Overflow("seed-to-soil".to_string(), 5),
+                Lint::Overlap("seed-to-soil".to_string(), 1, 2),
+                Lint::Gap("seed-to-soil".to_string(), 8..30),
+                Lint::Gap("seed-to-soil".to_string(), 32..40),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simplify() {
+        let almanac = parse_almanac(
+            "seeds: 1 2
+
+seed-to-soil map:
+10 0 5
+13 3 5
+7 7 3
+15 5 1
+16 6 1
+",
+        );
+        let simplified = simplify(&almanac);
+        assert_eq!(
+            simplified.to_string(),
+            "seeds: 1 2
+
+seed-to-soil map:
+10 0 8"
+        );
+        assert!(lint(&simplified)
+            .iter()
+            .all(|lint| matches!(lint, Lint::Gap(_, _))));
+
+        // two sections out of the same category keep their own lines
+        let almanac = parse_almanac(
+            "seeds: 1 2
+
+soil-to-water map:
+10 0 5
+15 5 2
+
+soil-to-light map:
+20 3 5
+",
+        );
+        assert!(lint(&almanac).is_empty());
+        assert_eq!(
+            simplify(&almanac).to_string(),
+            "seeds: 1 2
+
+soil-to-water map:
+10 0 7
+
+soil-to-light map:
+20 3 5"
+        );
+
+        let simplified = simplify(&ALMANAC);
+        let composed = compose(&ALMANAC, "seed", "location").unwrap();
+        let simplified_composed = compose(&simplified, "seed", "location").unwrap();
+        assert_eq!(composed, simplified_composed);
+        assert_eq!(
+            simplify(&generator(&simplified.to_string()).unwrap()).to_string(),
+            simplified.to_string()
+        );
+    }
+
     static INPUT: &str = "seeds: 79 14 55 13
 
 seed-to-soil map:
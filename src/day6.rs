@@ -1,66 +1,220 @@
-use std::iter::zip;
+use std::{fmt, iter::zip, ops::RangeInclusive};
 
 use itertools::Itertools;
+use num_bigint::{BigInt, BigUint};
 
 #[derive(Debug)]
 pub struct Race {
-    time: u64,
-    distance: u64,
+    time: BigUint,
+    distance: BigUint,
 }
 
-pub fn generator(input: &str) -> Vec<Race> {
+#[derive(Debug)]
+pub struct Races {
+    races: Vec<Race>,
+    // every column's digits run together
+    kerned: Race,
+}
+
+fn parse_race(time: &str, distance: &str) -> Race {
+    Race {
+        time: time.parse().unwrap(),
+        distance: distance.parse().unwrap(),
+    }
+}
+
+pub fn generator(input: &str) -> Races {
     let lines = input.lines().collect_vec();
-    let times = lines[0]
-        .split_whitespace()
-        .skip(1)
-        .map(|num| num.parse::<u64>().unwrap())
-        .collect_vec();
-    let distances = lines[1]
-        .split_whitespace()
-        .skip(1)
-        .map(|num| num.parse::<u64>().unwrap())
-        .collect_vec();
-    zip(&times, &distances)
-        .map(|(&time, &distance)| Race { time, distance })
-        .collect()
-}
-
-fn calc_race(hold_time: u64, total_time: u64) -> u64 {
+    let times = lines[0].split_whitespace().skip(1).collect_vec();
+    let distances = lines[1].split_whitespace().skip(1).collect_vec();
+    Races {
+        races: zip(&times, &distances)
+            .map(|(time, distance)| parse_race(time, distance))
+            .collect(),
+        kerned: parse_race(&times.concat(), &distances.concat()),
+    }
+}
+
+fn calc_race(hold_time: &BigUint, total_time: &BigUint) -> BigUint {
     let move_time = total_time - hold_time;
     move_time * hold_time
 }
 
-pub fn part_1(input: &[Race]) -> u64 {
-    input
-        .iter()
-        .map(|race| {
-            (1..race.time)
-                .filter(|&hold_time| calc_race(hold_time, race.time) > race.distance)
-                .count() as u64
+// h * (T - h) > D is symmetric around T / 2, so the winning hold times are
+// lo..=T - lo where lo sits just above the smaller root (T - sqrt(T^2 - 4D)) / 2.
+// The floor of the exact integer root is at most two steps below lo.
+fn winning_range(race: &Race) -> Option<RangeInclusive<BigUint>> {
+    let square = &race.time * &race.time;
+    let four_distance = &race.distance * 4_u32;
+    if square < four_distance {
+        return None;
+    }
+
+    let half = optimal_hold(race);
+    let mut lo = (&race.time - (square - four_distance).sqrt()) / 2_u32;
+    while lo <= half && calc_race(&lo, &race.time) <= race.distance {
+        lo += 1_u32;
+    }
+    (lo <= half).then(|| lo.clone()..=&race.time - &lo)
+}
+
+fn count_wins(race: &Race) -> BigUint {
+    winning_range(race).map_or(BigUint::default(), |range| {
+        range.end() - range.start() + 1_u32
+    })
+}
+
+// T is even or the two middle holds tie, so the floor of T / 2 is always optimal
+fn optimal_hold(race: &Race) -> BigUint {
+    &race.time / 2_u32
+}
+
+fn max_distance(race: &Race) -> BigUint {
+    calc_race(&optimal_hold(race), &race.time)
+}
+
+fn margin(race: &Race, hold_time: &BigUint) -> BigInt {
+    BigInt::from(calc_race(hold_time, &race.time)) - BigInt::from(race.distance.clone())
+}
+
+// Ranked by distance, the holds fan out from the middle: T / 2, then one step either
+// side, then two, ... (for odd T the two middle holds share the top spot). A record
+// leaves at least `wins` winners iff it is below the distance of the `wins`-th hold.
+fn largest_record(time: &BigUint, wins: &BigUint) -> Option<BigUint> {
+    if *wins == BigUint::default() || *wins > time + 1_u32 {
+        return None;
+    }
+    let offset = if time.bit(0) {
+        (wins - 1_u32) / 2_u32
+    } else {
+        wins / 2_u32
+    };
+    let hold_time = time / 2_u32 - offset;
+    let distance = calc_race(&hold_time, time);
+    (distance != BigUint::default()).then(|| distance - 1_u32)
+}
+
+pub struct Strategy {
+    time: BigUint,
+    record: BigUint,
+    optimal_hold: BigUint,
+    max_distance: BigUint,
+    winning: Option<RangeInclusive<BigUint>>,
+    wins: BigUint,
+    // (hold time, margin) for every hold of a short race, else only the landmarks
+    margins: Vec<(BigUint, BigInt)>,
+    min_wins: BigUint,
+    largest_record: Option<BigUint>,
+}
+
+// Races longer than this only list margins around the winning range and the optimum
+static MAX_LISTED_HOLDS: u32 = 64;
+
+fn listed_holds(race: &Race, winning: Option<&RangeInclusive<BigUint>>) -> Vec<BigUint> {
+    if race.time <= BigUint::from(MAX_LISTED_HOLDS) {
+        let mut holds = vec![];
+        let mut hold_time = BigUint::default();
+        while hold_time <= race.time {
+            holds.push(hold_time.clone());
+            hold_time += 1_u32;
+        }
+        return holds;
+    }
+
+    let mut holds = vec![BigUint::default(), optimal_hold(race), race.time.clone()];
+    if let Some(range) = winning {
+        // the last losing hold either side, if there is one
+        holds.extend([range.start() - 1_u32, range.start().clone()]);
+        holds.extend([range.end().clone(), range.end() + 1_u32]);
+    }
+    holds.into_iter().sorted().dedup().collect()
+}
+
+fn strategy(race: &Race, min_wins: &BigUint) -> Strategy {
+    let winning = winning_range(race);
+    let margins = listed_holds(race, winning.as_ref())
+        .into_iter()
+        .map(|hold_time| {
+            let margin = margin(race, &hold_time);
+            (hold_time, margin)
         })
-        .product()
+        .collect();
+    Strategy {
+        time: race.time.clone(),
+        record: race.distance.clone(),
+        optimal_hold: optimal_hold(race),
+        max_distance: max_distance(race),
+        winning,
+        wins: count_wins(race),
+        margins,
+        min_wins: min_wins.clone(),
+        largest_record: largest_record(&race.time, min_wins),
+    }
 }
 
-pub fn part_2(input: &[Race]) -> u64 {
-    let input = input.iter().fold(
-        Race {
-            time: 0,
-            distance: 0,
-        },
-        |acc, race| Race {
-            time: format!("{}{}", acc.time, race.time).parse().unwrap(),
-            distance: format!("{}{}", acc.distance, race.distance)
-                .parse()
-                .unwrap(),
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "time {}, record {}", self.time, self.record)?;
+        write!(f, "  optimal hold {}", self.optimal_hold)?;
+        if self.time.bit(0) {
+            write!(f, " (ties with {})", &self.optimal_hold + 1_u32)?;
+        }
+        writeln!(f, ", max distance {}", self.max_distance)?;
+        match &self.winning {
+            Some(range) => writeln!(
+                f,
+                "  {} wins, holding {}..={}",
+                self.wins,
+                range.start(),
+                range.end()
+            )?,
+            None => writeln!(f, "  no wins")?,
+        }
+        let margins = self
+            .margins
+            .iter()
+            .map(|(hold_time, margin)| format!("{hold_time}:{margin:+}"))
+            .join(" ");
+        writeln!(f, "  margins {margins}")?;
+        match &self.largest_record {
+            Some(record) => write!(
+                f,
+                "  largest record leaving {} wins: {record}",
+                self.min_wins
+            ),
+            None => write!(f, "  no record leaves {} wins", self.min_wins),
+        }
+    }
+}
+
+// The number of wins to leave is read from `DAY6_WINS`, e.g. `DAY6_WINS=3 cargo run -- -d 6`
+pub fn report(input: &Races) -> String {
+    let min_wins = match std::env::var("DAY6_WINS") {
+        Ok(wins) => match wins.parse() {
+            Ok(wins) => wins,
+            Err(err) => return format!("invalid DAY6_WINS: {err}"),
         },
-    );
-    let input = vec![input];
-    part_1(&input)
+        Err(_) => BigUint::from(1_u32),
+    };
+    input
+        .races
+        .iter()
+        .enumerate()
+        .map(|(i, race)| format!("Race {}: {}", i + 1, strategy(race, &min_wins)))
+        .join("\n")
+}
+
+pub fn part_1(input: &Races) -> BigUint {
+    input.races.iter().map(count_wins).product()
+}
+
+pub fn part_2(input: &Races) -> BigUint {
+    count_wins(&input.kerned)
 }
 
 #[cfg(test)]
 mod tests {
-    use insta::assert_debug_snapshot;
+    use insta::{assert_debug_snapshot, assert_snapshot};
 
     use super::*;
 
@@ -70,44 +224,174 @@ Distance:  9  40  200";
     #[test]
     fn test_generator() {
         assert_debug_snapshot!(generator(&INPUT), @r###"
-        [
-            Race {
-                time: 7,
-                distance: 9,
-            },
-            Race {
-                time: 15,
-                distance: 40,
-            },
-            Race {
-                time: 30,
-                distance: 200,
+        Races {
+            races: [
+                Race {
+                    time: 7,
+                    distance: 9,
+                },
+                Race {
+                    time: 15,
+                    distance: 40,
+                },
+                Race {
+                    time: 30,
+                    distance: 200,
+                },
+            ],
+            kerned: Race {
+                time: 71530,
+                distance: 940200,
             },
-        ]
+        }
         "###);
     }
 
+    fn race(time: u64, distance: u64) -> Race {
+        Race {
+            time: time.into(),
+            distance: distance.into(),
+        }
+    }
+
+    fn big(digits: &str) -> BigUint {
+        digits.parse().unwrap()
+    }
+
     #[test]
     fn test_calc_race() {
-        assert_eq!(calc_race(0, 7), 0);
-        assert_eq!(calc_race(1, 7), 6);
-        assert_eq!(calc_race(2, 7), 10);
-        assert_eq!(calc_race(3, 7), 12);
-        assert_eq!(calc_race(4, 7), 12);
-        assert_eq!(calc_race(5, 7), 10);
-        assert_eq!(calc_race(6, 7), 6);
-        assert_eq!(calc_race(7, 7), 0);
+        let results = (0..=7_u32)
+            .map(|hold_time| calc_race(&hold_time.into(), &7_u32.into()))
+            .collect_vec();
+        assert_eq!(results, [0_u32, 6, 10, 12, 12, 10, 6, 0].map(BigUint::from));
     }
 
     #[test]
     fn test_part_1() {
         let input = generator(&INPUT);
-        assert_eq!(part_1(&input), 288);
+        assert_eq!(part_1(&input), 288_u32.into());
     }
 
     #[test]
     fn test_part_2() {
         let input = generator(&INPUT);
-        assert_eq!(part_2(&input), 71503);
+        assert_eq!(part_2(&input), 71503_u32.into());
+    }
+
+    #[test]
+    fn test_count_wins() {
+        // against every hold time, including records that a hold time ties exactly
+        for time in 0..60_u64 {
+            for distance in 0..=time * time / 4 + 2 {
+                let brute = (0..=time)
+                    .filter(|&hold_time| hold_time * (time - hold_time) > distance)
+                    .count() as u64;
+                let race = race(time, distance);
+                assert_eq!(count_wins(&race), brute.into(), "{:?}", race);
+            }
+        }
+
+        assert_eq!(
+            winning_range(&race(7, 9)),
+            Some(2_u32.into()..=5_u32.into())
+        );
+        assert_eq!(
+            winning_range(&race(30, 200)),
+            Some(11_u32.into()..=19_u32.into())
+        );
+        // the best hold time only ties the record
+        assert_eq!(winning_range(&race(8, 16)), None);
+        assert_eq!(winning_range(&race(8, 17)), None);
+        assert_eq!(count_wins(&race(u64::MAX, u64::MAX)), (u64::MAX - 3).into());
+    }
+
+    #[test]
+    fn test_huge_races() {
+        // T = 2k and D = k^2 - m^2 leaves exactly the holds k - m < h < k + m
+        let k = big(&"9".repeat(150)) * big(&format!("7{}", "3".repeat(120)));
+        let m = big(&format!("1{}", "0".repeat(90)));
+        let time = &k * 2_u32;
+        let square = &k * &k;
+        let cases = [
+            (&square - &m * &m, &m * 2_u32 - 1_u32),
+            (square.clone(), BigUint::default()),
+            (&square - 1_u32, 1_u32.into()),
+        ];
+        for (distance, wins) in cases {
+            let race = Race {
+                time: time.clone(),
+                distance,
+            };
+            assert_eq!(count_wins(&race), wins);
+        }
+
+        // kerning dozens of columns still lands exactly on the record boundary
+        let times = (1..=40).map(|n| 100 + n).join(" ");
+        let distances = (1..=40).map(|n| 1000 + 37 * n).join(" ");
+        let input = generator(&format!("Time: {}\nDistance: {}", times, distances));
+        let kerned = &input.kerned;
+        assert_eq!(kerned.time.to_string().len(), 120);
+        let range = winning_range(kerned).unwrap();
+        let start = range.start();
+        assert!(calc_race(start, &kerned.time) > kerned.distance);
+        assert!(calc_race(&(start - 1_u32), &kerned.time) <= kerned.distance);
+        assert_eq!(range.end(), &(&kerned.time - start));
+
+        // only the landmarks around the winning range get a margin
+        let strategy = strategy(kerned, &1_u32.into());
+        let holds = strategy.margins.iter().map(|(hold, _)| hold).collect_vec();
+        assert_eq!(
+            holds,
+            [
+                BigUint::default(),
+                start - 1_u32,
+                start.clone(),
+                optimal_hold(kerned),
+                range.end().clone(),
+                range.end() + 1_u32,
+                kerned.time.clone(),
+            ]
+            .iter()
+            .collect_vec()
+        );
+        assert!(strategy.margins[1].1 <= BigInt::default());
+        assert!(strategy.margins[2].1 > BigInt::default());
+        assert!(strategy
+            .to_string()
+            .contains(&format!("{} wins", count_wins(kerned))));
+    }
+
+    #[test]
+    fn test_largest_record() {
+        for time in 0..40_u64 {
+            for wins in 0..=time + 2 {
+                // the highest record leaving at least `wins` winners, searched directly
+                let brute = (0..=time * time / 4)
+                    .rev()
+                    .find(|&distance| count_wins(&race(time, distance)) >= wins.into());
+                assert_eq!(
+                    largest_record(&time.into(), &wins.into()),
+                    brute.filter(|_| wins > 0).map(BigUint::from),
+                    "time {time}, wins {wins}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_strategy() {
+        let input = generator(INPUT);
+        let report = input
+            .races
+            .iter()
+            .map(|race| strategy(race, &4_u32.into()).to_string())
+            .join("\n");
+        assert_snapshot!(report);
+
+        let race = race(9, 14);
+        assert_eq!(optimal_hold(&race), 4_u32.into());
+        assert_eq!(max_distance(&race), 20_u32.into());
+        assert_eq!(margin(&race, &0_u32.into()), BigInt::from(-14));
+        assert_eq!(margin(&race, &5_u32.into()), BigInt::from(6));
     }
 }
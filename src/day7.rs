@@ -1,214 +1,609 @@
 use itertools::Itertools;
-use std::cmp::Ordering;
+use std::{collections::HashMap, fmt, str::FromStr};
 
-#[derive(Debug, Eq, Clone, Copy)]
-struct Hand([u32; 5]);
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct Hand(Vec<char>);
 
-#[derive(Debug, Eq)]
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.iter().collect::<String>())
+    }
+}
+
+#[derive(Debug)]
 pub struct Play {
     hand: Hand,
     bid: u32,
 }
 
+// The group sizes of a hand, largest first. Comparing them lexicographically gives the
+// puzzle's order for five cards (high card < one pair < ... < five of a kind) and extends
+// it to hands of any size.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct Category(Vec<usize>);
+
+const NAMES: [(&str, &[usize]); 7] = [
+    ("high", &[1, 1, 1, 1, 1]),
+    ("pair", &[2, 1, 1, 1]),
+    ("two-pair", &[2, 2, 1]),
+    ("three", &[3, 1, 1]),
+    ("full", &[3, 2]),
+    ("four", &[4, 1]),
+    ("five", &[5]),
+];
+
+impl Category {
+    // every category for hands of `size` cards, weakest first
+    fn all(size: usize) -> Vec<Category> {
+        fn partitions(size: usize, max: usize) -> Vec<Vec<usize>> {
+            if size == 0 {
+                return vec![vec![]];
+            }
+            (1..=max.min(size))
+                .flat_map(|part| {
+                    partitions(size - part, part).into_iter().map(move |rest| {
+                        let mut groups = vec![part];
+                        groups.extend(rest);
+                        groups
+                    })
+                })
+                .collect()
+        }
+        partitions(size, size)
+            .into_iter()
+            .map(Category)
+            .sorted()
+            .collect()
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match NAMES.iter().find(|(_, groups)| *groups == self.0) {
+            Some((name, _)) => write!(f, "{name}"),
+            None => write!(f, "{}", self.0.iter().join("+")),
+        }
+    }
+}
+
+// A five-card name such as `two-pair`, or the group sizes joined by `+`, e.g. `3+3+1`
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, groups)) = NAMES.iter().find(|(name, _)| *name == s) {
+            return Ok(Category(groups.to_vec()));
+        }
+        let groups = s
+            .split('+')
+            .map(|group| group.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid category `{s}`"))?;
+        if groups.contains(&0) || groups.windows(2).any(|pair| pair[0] < pair[1]) {
+            return Err(format!("invalid category `{s}`"));
+        }
+        Ok(Category(groups))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    // weakest first
+    labels: Vec<char>,
+    wild: Vec<char>,
+    size: usize,
+    // the strength of each category under a custom `categories=` table, or `None` when
+    // categories keep their natural order
+    table: Option<HashMap<Category, usize>>,
+}
+
+// How a category ranks under one rule set. Only strengths from the same rule set compare.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub enum HandRank {
-    High,
-    Pair,
-    TwoPair,
-    Three,
-    Full,
-    Four,
-    Five,
+enum Strength {
+    Natural(Category),
+    Table(usize),
 }
 
-impl Hand {
-    fn hand_rank(&self) -> HandRank {
-        let Hand(hand) = self;
-        let groups = hand.iter().counts();
+impl RuleSet {
+    fn standard(size: usize) -> Self {
+        RuleSet {
+            labels: "23456789TJQKA".chars().collect(),
+            wild: vec![],
+            size,
+            table: None,
+        }
+    }
 
-        // handle jokers (1)
-        let hand = if groups.contains_key(&1) {
-            // println!("contains joker {:?}", &hand);
-            let max = hand.iter().max().unwrap();
-            let new_hand: [u32; 5] = hand
+    fn jokers(size: usize) -> Self {
+        RuleSet {
+            labels: "J23456789TQKA".chars().collect(),
+            wild: vec!['J'],
+            ..RuleSet::standard(size)
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn value(&self, card: char) -> usize {
+        self.labels.iter().position(|&label| label == card).unwrap()
+    }
+
+    fn category(&self, hand: &Hand) -> Category {
+        let Hand(hand) = hand;
+        let wild = hand.iter().filter(|card| self.wild.contains(card)).count();
+        let mut groups = hand
+            .iter()
+            .filter(|card| !self.wild.contains(card))
+            .counts()
+            .into_values()
+            .sorted()
+            .rev()
+            .collect_vec();
+
+        if self.table.is_some() {
+            let labels = self
+                .labels
                 .iter()
-                .map(|val| match val {
-                    1 => *max,
-                    val => *val,
-                })
-                .collect_vec()
-                .try_into()
+                .filter(|label| !self.wild.contains(label))
+                .count();
+            return reachable(groups, wild, labels)
+                .into_iter()
+                .map(Category)
+                .max_by_key(|category| self.strength(category))
                 .unwrap();
-            // dbg!(&hand, &max, &new_hand);
-            // println!("new hand {:?}", &res);
-            new_hand
-        } else {
-            *hand
-        };
-
-        let groups = hand.iter().counts();
-        let key_count = groups.keys().count();
+        }
 
-        match key_count {
-            5 => HandRank::High,
-            4 => HandRank::Pair,
-            3 => {
-                if groups.values().any(|&hits| hits == 3) {
-                    HandRank::Three
-                } else {
-                    HandRank::TwoPair
-                }
-            }
-            2 => match groups.values().next().unwrap() {
-                4 | 1 => HandRank::Four,
-                2 | 3 => HandRank::Full,
-                _ => {
-                    // dbg!(&hand, &groups);
-                    unreachable!();
-                }
-            },
-            1 => HandRank::Five,
-            _ => unreachable!(),
+        // In the natural order every wild card is best spent growing the largest group:
+        // any other substitution leaves a signature that is no greater.
+        match groups.first_mut() {
+            Some(largest) => *largest += wild,
+            None => groups.push(wild),
         }
+        Category(groups)
     }
 
-    // remap joker to 1
-    fn remap_joker(&self) -> Self {
+    // The hand with every wild card swapped for the label that gets it its category.
+    fn effective(&self, hand: &Hand) -> Hand {
+        let Category(target) = self.category(hand);
+        // groups by size and then value, largest first, so extra wild cards go to the
+        // strongest of equal groups
+        let groups = hand
+            .0
+            .iter()
+            .filter(|card| !self.wild.contains(card))
+            .counts()
+            .into_iter()
+            .sorted_by_key(|&(&card, count)| (count, self.value(card)))
+            .rev()
+            .collect_vec();
+        // Matching the largest group to the largest part and so on always fits, and the
+        // parts left over are filled by labels missing from the hand, strongest first.
+        let mut unused = self
+            .labels
+            .iter()
+            .rev()
+            .filter(|label| !hand.0.contains(label) && !self.wild.contains(label));
+        let mut missing = target
+            .iter()
+            .enumerate()
+            .map(|(i, &part)| match groups.get(i) {
+                Some(&(&card, count)) => (card, part - count),
+                None => (*unused.next().unwrap(), part),
+            })
+            .collect_vec();
         Hand(
-            self.0
+            hand.0
                 .iter()
-                .map(|&val| match val {
-                    11 => 1,
-                    val => val,
+                .map(|&card| {
+                    if !self.wild.contains(&card) {
+                        return card;
+                    }
+                    let (label, count) = missing.iter_mut().find(|(_, count)| *count > 0).unwrap();
+                    *count -= 1;
+                    *label
                 })
-                .collect_vec()
-                .try_into()
-                .unwrap(),
+                .collect(),
         )
     }
-}
 
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let Hand(self_hand) = self;
-        let Hand(other_hand) = other;
-        match self.hand_rank().cmp(&other.hand_rank()) {
-            Ordering::Equal => {
-                // println!("equal rank? {:?} == {:?}", &self_hand, &other_hand);
-                if let Some(ordering) =
-                    self_hand
-                        .iter()
-                        .zip(other_hand.iter())
-                        .find_map(|(&a, &b)| match a.cmp(&b) {
-                            Ordering::Equal => None,
-                            ordering => Some(ordering),
-                        })
-                {
-                    // println!("resolved to {:?}", ordering);
-                    ordering
-                } else {
-                    // println!("equal? {:?} == {:?}", &self_hand, &other_hand);
-                    Ordering::Equal
-                }
-            }
-            ordering => ordering,
+    fn strength(&self, category: &Category) -> Strength {
+        match &self.table {
+            Some(table) => Strength::Table(table[category]),
+            None => Strength::Natural(category.clone()),
         }
     }
-}
 
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    // An upper bound on packed strengths: a table's positions, or a natural category's
+    // group sizes packed as `size` digits in base `size + 1`.
+    fn strengths(&self) -> Option<u128> {
+        match &self.table {
+            Some(table) => Some(table.len() as u128),
+            None => (self.size as u128 + 1).checked_pow(self.size.try_into().ok()?),
+        }
     }
-}
 
-impl PartialEq for Hand {
-    fn eq(&self, other: &Self) -> bool {
-        self.0.iter().zip(other.0.iter()).all(|(a, b)| a == b)
+    // Whether every key for this hand size fits in a `u128`, i.e. strengths * radix^size does
+    fn packs(&self) -> bool {
+        let radix = self.labels.len() as u128;
+        u32::try_from(self.size)
+            .ok()
+            .and_then(|size| radix.checked_pow(size))
+            .zip(self.strengths())
+            .and_then(|(digits, strengths)| digits.checked_mul(strengths))
+            .is_some()
     }
-}
 
-impl Ord for Play {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.hand.cmp(&other.hand)
+    // The strength followed by each card's value as digits in base `labels.len()`, so
+    // comparing keys compares categories first and then the cards in order. Hands too
+    // long to pack keep the values as a list, decided per rule set so keys always compare.
+    fn key(&self, hand: &Hand) -> SortKey {
+        let strength = self.strength(&self.category(hand));
+        let values = hand.0.iter().map(|&card| self.value(card));
+        if !self.packs() {
+            return SortKey::Wide(strength, values.collect());
+        }
+        let strength = match strength {
+            Strength::Table(index) => index as u128,
+            // padding with zeros keeps the lexicographic order of the groups
+            Strength::Natural(Category(groups)) => (0..self.size).fold(0, |key, i| {
+                key * (self.size as u128 + 1) + groups.get(i).copied().unwrap_or(0) as u128
+            }),
+        };
+        let radix = self.labels.len() as u128;
+        SortKey::Packed(values.fold(strength, |key, value| key * radix + value as u128))
     }
 }
 
-impl PartialOrd for Play {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+enum SortKey {
+    Packed(u128),
+    Wide(Strength, Vec<usize>),
+}
+
+// Every signature the wild cards can make: each joins an existing group or starts a new
+// one, as long as there is a label left for it among the `labels` that are not wild.
+fn reachable(groups: Vec<usize>, wild: usize, labels: usize) -> Vec<Vec<usize>> {
+    let mut signatures = vec![groups];
+    for _ in 0..wild {
+        signatures = signatures
+            .iter()
+            .flat_map(|groups| {
+                let slots = if groups.len() < labels {
+                    groups.len() + 1
+                } else {
+                    groups.len()
+                };
+                (0..slots).map(|i| {
+                    let mut next = groups.clone();
+                    match next.get_mut(i) {
+                        Some(group) => *group += 1,
+                        None => next.push(1),
+                    }
+                    next.sort_unstable_by(|a, b| b.cmp(a));
+                    next
+                })
+            })
+            .unique()
+            .collect();
     }
+    signatures
 }
 
-impl PartialEq for Play {
-    fn eq(&self, other: &Self) -> bool {
-        self.hand == other.hand
+// Whitespace separated `key=value` pairs, strongest first like the puzzle, e.g.
+// `labels=KQJT98765432A wild=2 categories=five,four,three,full,two-pair,pair,high`,
+// with anything left out taken from the part 1 rules for five cards.
+impl FromStr for RuleSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = RuleSet::standard(5);
+        let mut size = 5;
+        let mut categories = None;
+        for pair in s.split_whitespace() {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected `key=value`, got `{pair}`"))?;
+            let invalid = || format!("invalid {key} `{value}`");
+            match key {
+                "labels" => {
+                    rules.labels = value.chars().rev().collect();
+                    if !rules.labels.iter().all_unique() {
+                        return Err(invalid());
+                    }
+                }
+                "wild" => rules.wild = value.chars().collect(),
+                "size" => {
+                    size = match value.parse() {
+                        Ok(size) if size > 0 => size,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "categories" => {
+                    categories = Some(value.split(',').rev().map(str::parse).collect::<Result<
+                        Vec<Category>,
+                        _,
+                    >>(
+                    )?)
+                }
+                _ => return Err(format!("unknown rule `{key}`")),
+            }
+        }
+        rules.size = size;
+        if let Some(categories) = categories {
+            if categories.iter().sorted().ne(&Category::all(size)) {
+                return Err(format!(
+                    "categories must order every {size}-card hand exactly once"
+                ));
+            }
+            // a table in the natural order needs no lookups
+            if !categories.is_sorted() {
+                rules.table = Some(
+                    categories
+                        .into_iter()
+                        .enumerate()
+                        .map(|(strength, category)| (category, strength))
+                        .collect(),
+                );
+            }
+        }
+        if let Some(card) = rules.wild.iter().find(|card| !rules.labels.contains(card)) {
+            return Err(format!("wild card `{card}` has no label"));
+        }
+        if rules.labels.iter().all(|label| rules.wild.contains(label)) {
+            return Err("every label is wild".to_string());
+        }
+        Ok(rules)
     }
 }
 
 fn parse_hand(hand: &str) -> Hand {
-    Hand(
-        hand.chars()
-            .map(map_char_to_value)
-            .collect_vec()
-            .try_into()
-            .unwrap(),
-    )
+    Hand(hand.chars().collect())
 }
 
-pub fn generator(input: &str) -> Vec<Play> {
-    input
+pub fn generator(input: &str) -> Result<Vec<Play>, String> {
+    let labels = RuleSet::standard(5).labels;
+    let plays: Vec<Play> = input
         .trim()
         .lines()
         .map(|line| {
-            let split = line.split_whitespace().collect_vec();
-            Play {
-                hand: parse_hand(split[0]),
-                bid: split[1].parse().unwrap(),
+            let [hand, bid] = line.split_whitespace().collect_vec()[..] else {
+                return Err(format!("expected `<hand> <bid>`, got `{line}`"));
+            };
+            // variants may only reorder or wild the puzzle's labels
+            if let Some(card) = hand.chars().find(|card| !labels.contains(card)) {
+                return Err(format!("card `{card}` has no label"));
             }
+            Ok(Play {
+                hand: parse_hand(hand),
+                bid: bid
+                    .parse()
+                    .map_err(|err| format!("invalid bid `{bid}`: {err}"))?,
+            })
         })
-        .collect()
+        .try_collect()?;
+    if let Some(first) = plays.first() {
+        let size = first.hand.0.len();
+        if let Some((line, play)) = plays
+            .iter()
+            .enumerate()
+            .find(|(_, play)| play.hand.0.len() != size)
+        {
+            return Err(format!(
+                "hand {} has {} cards, expected {size}",
+                line + 1,
+                play.hand.0.len()
+            ));
+        }
+    }
+    Ok(plays)
 }
 
-fn map_char_to_value(card: char) -> u32 {
-    match card {
-        'A' => 14,
-        'K' => 13,
-        'Q' => 12,
-        'J' => 11,
-        'T' => 10,
-        card => card.to_digit(10).unwrap(),
-    }
+fn hand_size(input: &[Play]) -> usize {
+    input.first().map_or(5, |play| play.hand.0.len())
 }
 
-pub fn part_1(input: &[Play]) -> u32 {
+// weakest first, with the sort key each play was ranked by
+fn ranked<'a>(input: &'a [Play], rules: &RuleSet) -> Vec<(SortKey, &'a Play)> {
     input
         .iter()
-        .sorted()
+        .map(|play| (rules.key(&play.hand), play))
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .collect()
+}
+
+fn winnings(input: &[Play], rules: &RuleSet) -> u32 {
+    ranked(input, rules)
+        .iter()
         .enumerate()
-        .map(|(index, play)| (index as u32 + 1) * play.bid)
+        .map(|(index, (_, play))| (index as u32 + 1) * play.bid)
         .sum()
 }
 
-// too low 247281796
-pub fn part_2(input: &[Play]) -> u32 {
-    input
+pub struct Standing {
+    rank: u32,
+    hand: Hand,
+    category: Category,
+    effective: Hand,
+    bid: u32,
+    winnings: u32,
+}
+
+pub struct Standings {
+    rows: Vec<Standing>,
+    // ranks of hands that compare equal, which take their order from the input
+    ties: Vec<Vec<u32>>,
+}
+
+fn standings(input: &[Play], rules: &RuleSet) -> Standings {
+    let ranked = ranked(input, rules);
+    let rows = ranked
         .iter()
-        .map(|play| Play {
-            hand: play.hand.remap_joker(),
+        .enumerate()
+        .map(|(index, (_, play))| Standing {
+            rank: index as u32 + 1,
+            hand: play.hand.clone(),
+            category: rules.category(&play.hand),
+            effective: rules.effective(&play.hand),
             bid: play.bid,
+            winnings: (index as u32 + 1) * play.bid,
         })
-        .sorted()
+        .collect();
+    let ties = ranked
+        .iter()
         .enumerate()
-        .map(|(index, play)| (index as u32 + 1) * play.bid)
-        .sum()
+        .group_by(|(_, (key, _))| key)
+        .into_iter()
+        .map(|(_, group)| group.map(|(index, _)| index as u32 + 1).collect_vec())
+        .filter(|ranks| ranks.len() > 1)
+        .collect();
+    Standings { rows, ties }
+}
+
+impl Standings {
+    fn total(&self) -> u32 {
+        self.rows.iter().map(|row| row.winnings).sum()
+    }
+
+    fn csv(&self) -> String {
+        let rows = self.rows.iter().map(|row| {
+            format!(
+                "{},{},{},{},{},{}",
+                row.rank, row.hand, row.category, row.effective, row.bid, row.winnings
+            )
+        });
+        ["rank,hand,category,effective,bid,winnings".to_string()]
+            .into_iter()
+            .chain(rows)
+            .join("\n")
+    }
+}
+
+impl fmt::Display for Standings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = ["rank", "hand", "category", "effective", "bid", "winnings"];
+        let cells = self
+            .rows
+            .iter()
+            .map(|row| {
+                [
+                    row.rank.to_string(),
+                    row.hand.to_string(),
+                    row.category.to_string(),
+                    row.effective.to_string(),
+                    row.bid.to_string(),
+                    row.winnings.to_string(),
+                ]
+            })
+            .collect_vec();
+        let widths = (0..header.len())
+            .map(|i| {
+                cells
+                    .iter()
+                    .map(|row| row[i].len())
+                    .chain([header[i].len()])
+                    .max()
+                    .unwrap()
+            })
+            .collect_vec();
+        // numbers to the right, text to the left
+        let line = |row: &[&str]| {
+            row.iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(i, (cell, &width))| match i {
+                    0 | 4 | 5 => format!("{cell:>width$}"),
+                    _ => format!("{cell:<width$}"),
+                })
+                .join("  ")
+        };
+        writeln!(f, "{}", line(&header))?;
+        for row in &cells {
+            writeln!(f, "{}", line(&row.iter().map(String::as_str).collect_vec()))?;
+        }
+        writeln!(f, "total winnings {}", self.total())?;
+        if self.ties.is_empty() {
+            return write!(f, "no ties");
+        }
+        for ranks in &self.ties {
+            let hand = &self.rows[ranks[0] as usize - 1].hand;
+            write!(f, "\ntie: {hand} at ranks {}", ranks.iter().join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn part_1(input: &[Play]) -> u32 {
+    winnings(input, &RuleSet::standard(hand_size(input)))
+}
+
+// too low 247281796
+pub fn part_2(input: &[Play]) -> u32 {
+    winnings(input, &RuleSet::jokers(hand_size(input)))
+}
+
+// Rules for these plays from text such as `DAY7_RULES`, with every hand checked against them
+fn load_rules(input: &[Play], rules: &str) -> Result<RuleSet, String> {
+    let rules = rules
+        .parse::<RuleSet>()
+        .map_err(|err| format!("invalid rules: {err}"))?;
+    let size = hand_size(input);
+    if size != rules.size() {
+        return Err(format!(
+            "hands have {size} cards, set size={size} to play them"
+        ));
+    }
+    let unlabelled = input
+        .iter()
+        .flat_map(|play| &play.hand.0)
+        .find(|card| !rules.labels.contains(card));
+    match unlabelled {
+        Some(card) => Err(format!("card `{card}` has no label")),
+        None => Ok(rules),
+    }
+}
+
+// The rules are read from `DAY7_RULES`, e.g. `DAY7_RULES="wild=2" cargo run -- -d 7`
+pub fn variant(input: &[Play]) -> String {
+    let Ok(rules) = std::env::var("DAY7_RULES") else {
+        return "set DAY7_RULES to play a variant".to_string();
+    };
+    match load_rules(input, &rules) {
+        Ok(rules) => winnings(input, &rules).to_string(),
+        Err(err) => err,
+    }
+}
+
+// Part 2 unless `DAY7_RULES` is set
+fn standings_rules(input: &[Play]) -> Result<RuleSet, String> {
+    match std::env::var("DAY7_RULES") {
+        Ok(rules) => load_rules(input, &rules),
+        Err(_) => Ok(RuleSet::jokers(hand_size(input))),
+    }
+}
+
+pub fn table(input: &[Play]) -> String {
+    match standings_rules(input) {
+        Ok(rules) => standings(input, &rules).to_string(),
+        Err(err) => err,
+    }
+}
+
+pub fn csv(input: &[Play]) -> String {
+    match standings_rules(input) {
+        Ok(rules) => standings(input, &rules).csv(),
+        Err(err) => err,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use insta::assert_debug_snapshot;
+    use insta::{assert_debug_snapshot, assert_snapshot};
+    use std::cmp::Ordering;
 
     use super::*;
+    use crate::test_util::Lcg;
 
     static INPUT: &str = "32T3K 765
 T55J5 684
@@ -216,116 +611,350 @@ KK677 28
 KTJJT 220
 QQQJA 483";
 
+    fn rank(rules: &RuleSet, hand: &str) -> String {
+        rules.category(&parse_hand(hand)).to_string()
+    }
+
+    fn compare(rules: &RuleSet, a: &str, b: &str) -> Ordering {
+        rules.key(&parse_hand(a)).cmp(&rules.key(&parse_hand(b)))
+    }
+
+    // the comparison the keys stand in for
+    fn reference_cmp(rules: &RuleSet, a: &Hand, b: &Hand) -> Ordering {
+        let strength = |hand| rules.strength(&rules.category(hand));
+        strength(a).cmp(&strength(b)).then_with(|| {
+            a.0.iter()
+                .map(|&card| rules.value(card))
+                .cmp(b.0.iter().map(|&card| rules.value(card)))
+        })
+    }
+
+    // `count` hands drawn from a linear congruential generator
+    fn random_plays(count: usize) -> Vec<Play> {
+        let labels = RuleSet::standard(5).labels;
+        let mut rng = Lcg::new(0x2545_f491_4f6c_dd1d);
+        let mut next = move || rng.next() as usize;
+        (0..count)
+            .map(|_| Play {
+                hand: Hand((0..5).map(|_| labels[next() % labels.len()]).collect()),
+                bid: (next() % 1000) as u32 + 1,
+            })
+            .collect()
+    }
+
     #[test]
     fn test_generator() {
-        assert_debug_snapshot!(generator(&INPUT));
+        assert_debug_snapshot!(generator(INPUT).unwrap());
     }
 
     #[test]
     fn test_hand_rank() {
-        assert_eq!(HandRank::Five.cmp(&HandRank::Four), Ordering::Greater);
-        assert_eq!(Hand([13, 13, 13, 13, 13]).hand_rank(), HandRank::Five);
-        assert_eq!(Hand([12, 13, 13, 13, 13]).hand_rank(), HandRank::Four);
-        assert_eq!(Hand([12, 12, 13, 13, 13]).hand_rank(), HandRank::Full);
-        assert_eq!(Hand([11, 12, 13, 13, 13]).hand_rank(), HandRank::Three);
-        assert_eq!(Hand([11, 12, 12, 13, 13]).hand_rank(), HandRank::TwoPair);
-        assert_eq!(Hand([11, 12, 10, 13, 13]).hand_rank(), HandRank::Pair);
-        assert_eq!(Hand([11, 12, 10, 9, 13]).hand_rank(), HandRank::High);
+        let rules = RuleSet::standard(5);
+        assert!("five".parse::<Category>() > "four".parse::<Category>());
+        assert_eq!(rank(&rules, "KKKKK"), "five");
+        assert_eq!(rank(&rules, "QKKKK"), "four");
+        assert_eq!(rank(&rules, "QQKKK"), "full");
+        assert_eq!(rank(&rules, "JQKKK"), "three");
+        assert_eq!(rank(&rules, "JQQKK"), "two-pair");
+        assert_eq!(rank(&rules, "JQTKK"), "pair");
+        assert_eq!(rank(&rules, "JQT9K"), "high");
 
         // With active jokers
+        let rules = RuleSet::jokers(5);
+        assert_eq!(rank(&rules, "JJJJJ"), "five");
+        assert_eq!(rank(&rules, "QJJJJ"), "five");
+        assert_eq!(rank(&rules, "QQJJJ"), "five");
+        assert_eq!(rank(&rules, "2QJJJ"), "four");
+        assert_eq!(rank(&rules, "2QQJJ"), "four");
+        assert_eq!(rank(&rules, "2QTJJ"), "three");
+        assert_eq!(rank(&rules, "2QT9J"), "pair");
+    }
+
+    #[test]
+    fn test_hand_ordering() {
+        let plays = generator(INPUT).unwrap();
+        let rules = RuleSet::standard(5);
+        assert_debug_snapshot!(plays
+            .iter()
+            .sorted_by_key(|play| rules.key(&play.hand))
+            .collect_vec());
+
+        let rules = RuleSet::jokers(5);
+        assert_eq!(rank(&rules, "QQQQ2"), "four");
+        assert_eq!(rank(&rules, "JKKK2"), "four");
+        assert_eq!(rank(&rules, "KKKJ2"), "four");
+        assert_eq!(compare(&rules, "QQQQ2", "JKKK2"), Ordering::Greater);
+        assert_eq!(compare(&rules, "QQQQ2", "KKKJ2"), Ordering::Less);
+        assert_eq!(compare(&rules, "JQQQ2", "KKKK2"), Ordering::Less);
+        assert_eq!(compare(&rules, "JJJJJ", "22222"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_part_1() {
+        let input = generator(INPUT).unwrap();
+        assert_eq!(part_1(&input), 6440);
+    }
+
+    #[test]
+    fn test_part_2() {
+        let input = generator(INPUT).unwrap();
+        assert_eq!(part_2(&input), 5905);
+    }
+
+    #[test]
+    fn test_joker_substitution() {
+        assert_eq!(rank(&RuleSet::jokers(5), "22KJ5"), "three");
+        assert_eq!(rank(&RuleSet::jokers(5), "2JK3J"), "three");
+
+        // every multiset of cards, against the best of every way to fill in the jokers, for
+        // the natural categories and for a table where three of a kind beats a full house
+        let reordered: RuleSet = "labels=AKQT98765432J wild=J \
+            categories=five,four,three,full,two-pair,pair,high"
+            .parse()
+            .unwrap();
+        // and for a table favouring many groups with too few labels to make them
+        let few_labels: RuleSet = "labels=AKJ wild=J \
+            categories=high,pair,two-pair,three,full,four,five"
+            .parse()
+            .unwrap();
+        assert_eq!(rank(&few_labels, "JJJJJ"), "full");
+        assert_eq!(
+            few_labels.effective(&parse_hand("JJJJJ")).to_string(),
+            "AAAKK"
+        );
+        assert_eq!(
+            few_labels.effective(&parse_hand("KJJJA")).to_string(),
+            "KAAKA"
+        );
+        for rules in [
+            RuleSet::jokers(5),
+            reordered,
+            RuleSet::jokers(3),
+            few_labels,
+        ] {
+            let natural = RuleSet::standard(rules.size());
+            for cards in rules
+                .labels
+                .iter()
+                .copied()
+                .combinations_with_replacement(rules.size())
+            {
+                let hand = Hand(cards);
+                let brute = hand
+                    .0
+                    .iter()
+                    .map(|&card| match card {
+                        'J' => rules.labels[1..].to_vec(),
+                        card => vec![card],
+                    })
+                    .multi_cartesian_product()
+                    .map(|cards| rules.strength(&natural.category(&Hand(cards))))
+                    .max()
+                    .unwrap();
+                assert_eq!(rules.strength(&rules.category(&hand)), brute, "{:?}", hand);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_set() {
+        let ace_low: RuleSet = "labels=KQJT98765432A".parse().unwrap();
+        assert_eq!(compare(&ace_low, "A2345", "23456"), Ordering::Less);
         assert_eq!(
-            Hand([11, 11, 11, 11, 11]).remap_joker().hand_rank(),
-            HandRank::Five
+            compare(&RuleSet::standard(5), "A2345", "23456"),
+            Ordering::Greater
         );
+
+        let wild_twos: RuleSet = "wild=2".parse().unwrap();
+        assert_eq!(rank(&wild_twos, "2345A"), "pair");
+        assert_eq!(rank(&wild_twos, "22KQK"), "four");
+        assert_eq!(compare(&wild_twos, "2KKKK", "JJJJ3"), Ordering::Greater);
+
+        let three_over_full: RuleSet = "categories=five,four,three,full,two-pair,pair,high"
+            .parse()
+            .unwrap();
+        assert_eq!(compare(&three_over_full, "33322", "3332K"), Ordering::Less);
+
         assert_eq!(
-            Hand([12, 11, 11, 11, 11]).remap_joker().hand_rank(),
-            HandRank::Five
+            "wild=X".parse::<RuleSet>().unwrap_err(),
+            "wild card `X` has no label"
         );
         assert_eq!(
-            Hand([12, 12, 11, 11, 11]).remap_joker().hand_rank(),
-            HandRank::Five
+            "labels=J wild=J".parse::<RuleSet>().unwrap_err(),
+            "every label is wild"
         );
         assert_eq!(
-            Hand([2, 12, 11, 11, 11]).remap_joker().hand_rank(),
-            HandRank::Four
+            "labels=AAK".parse::<RuleSet>().unwrap_err(),
+            "invalid labels `AAK`"
         );
         assert_eq!(
-            Hand([2, 12, 12, 11, 11]).remap_joker().hand_rank(),
-            HandRank::Four
+            "categories=five,four".parse::<RuleSet>().unwrap_err(),
+            "categories must order every 5-card hand exactly once"
         );
         assert_eq!(
-            Hand([2, 12, 10, 11, 11]).remap_joker().hand_rank(),
-            HandRank::Three
+            "categories=five,straight".parse::<RuleSet>().unwrap_err(),
+            "invalid category `straight`"
         );
         assert_eq!(
-            Hand([2, 12, 10, 9, 11]).remap_joker().hand_rank(),
-            HandRank::Pair
+            "jokers".parse::<RuleSet>().unwrap_err(),
+            "expected `key=value`, got `jokers`"
         );
     }
 
     #[test]
-    fn test_hand_ordering() {
-        let plays = generator(&INPUT);
-        assert_debug_snapshot!(plays.iter().sorted().collect_vec());
+    fn test_key() {
+        let plays = random_plays(20_000);
+        for rules in [RuleSet::standard(5), RuleSet::jokers(5)] {
+            for (a, b) in plays.iter().tuple_windows() {
+                assert_eq!(
+                    rules.key(&a.hand).cmp(&rules.key(&b.hand)),
+                    reference_cmp(&rules, &a.hand, &b.hand),
+                    "{:?} {:?}",
+                    a.hand,
+                    b.hand
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_keys() {
+        let rules = RuleSet::standard(40);
+        assert!(!rules.packs());
+        assert!(RuleSet::standard(5).packs());
+
+        let input = generator(&format!(
+            "{} 1\n{} 2\n{}K 3",
+            "A".repeat(40),
+            "K".repeat(40),
+            "A".repeat(39)
+        ))
+        .unwrap();
+        // AAA...K < KKK... < AAA...
+        assert_eq!(part_1(&input), 3 + 2 * 2 + 3);
+
+        // no rule set lists every category, so long hands stay cheap
+        for rules in [rules, RuleSet::jokers(40), RuleSet::jokers(70)] {
+            let hands = random_plays(200)
+                .into_iter()
+                .map(|play| Hand(play.hand.0.repeat(rules.size() / 5)))
+                .collect_vec();
+            for (a, b) in hands.iter().tuple_windows() {
+                assert_eq!(
+                    rules.key(a).cmp(&rules.key(b)),
+                    reference_cmp(&rules, a, b),
+                    "{a} {b}"
+                );
+            }
+        }
+    }
+
+    // cargo test --release day7::tests::bench_million_hands -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_million_hands() {
+        let plays = random_plays(1_000_000);
+        for rules in [RuleSet::standard(5), RuleSet::jokers(5)] {
+            let start = std::time::Instant::now();
+            let by_key = plays
+                .iter()
+                .sorted_by_cached_key(|play| rules.key(&play.hand))
+                .map(|play| play.hand.clone())
+                .collect_vec();
+            let keyed = start.elapsed();
+
+            let start = std::time::Instant::now();
+            let by_cmp = plays
+                .iter()
+                .sorted_by(|a, b| reference_cmp(&rules, &a.hand, &b.hand))
+                .map(|play| play.hand.clone())
+                .collect_vec();
+            let compared = start.elapsed();
 
-        let a = Hand([12, 12, 12, 12, 2]).remap_joker();
-        assert_eq!(a.hand_rank(), HandRank::Four);
-        let b = Hand([11, 13, 13, 13, 2]).remap_joker();
-        assert_eq!(b.hand_rank(), HandRank::Four);
+            println!("keys {keyed:?}, comparisons {compared:?}");
+            assert_eq!(by_key, by_cmp);
+        }
+    }
+
+    #[test]
+    fn test_hand_sizes() {
+        assert_eq!(Category::all(3).iter().join(" "), "1+1+1 2+1 3");
         assert_eq!(
-            Hand([13, 13, 13, 11, 2]).remap_joker().hand_rank(),
-            HandRank::Four
+            Category::all(5).iter().join(" "),
+            NAMES.map(|(name, _)| name).join(" ")
         );
-
+        assert_eq!(Category::all(7).len(), 15);
+
+        let input = generator("AKQ 1\nJJ2 2\n2AA 3\n22J 4\nQQQ 5").unwrap();
+        assert_eq!(rank(&RuleSet::standard(3), "2AA"), "2+1");
+        assert_eq!(rank(&RuleSet::jokers(3), "22J"), "3");
+        // AKQ < 22J < 2AA < JJ2 < QQQ
+        assert_eq!(part_1(&input), 1 + 2 * 4 + 3 * 3 + 4 * 2 + 5 * 5);
+        // AKQ < 2AA < JJ2 < 22J < QQQ
+        assert_eq!(part_2(&input), 1 + 2 * 3 + 3 * 2 + 4 * 4 + 5 * 5);
+
+        let input = generator("2233445 10\nJJ23456 20\n2223334 30\nAKQT987 40").unwrap();
+        let jokers = RuleSet::jokers(7);
+        assert_eq!(rank(&jokers, "2233445"), "2+2+2+1");
+        assert_eq!(rank(&jokers, "JJ23456"), "3+1+1+1+1");
+        assert_eq!(rank(&jokers, "2223334"), "3+3+1");
+        assert_eq!(part_1(&input), 40 + 2 * 20 + 3 * 10 + 4 * 30);
+        assert_eq!(part_2(&input), 40 + 2 * 10 + 3 * 20 + 4 * 30);
+
+        // a full house of four and three beats five of a kind with two kickers
+        let rules: RuleSet = "size=7 categories=7,6+1,5+2,4+3,5+1+1,4+2+1,3+3+1,4+1+1+1,\
+            3+2+2,3+2+1+1,2+2+2+1,3+1+1+1+1,2+2+1+1+1,2+1+1+1+1+1,1+1+1+1+1+1+1"
+            .parse()
+            .unwrap();
+        assert_eq!(compare(&rules, "AAAAAKQ", "AAAAKKK"), Ordering::Less);
         assert_eq!(
-            Hand([12, 12, 12, 12, 2])
-                .remap_joker()
-                .cmp(&Hand([11, 13, 13, 13, 2]).remap_joker()),
+            compare(&RuleSet::standard(7), "AAAAAKQ", "AAAAKKK"),
             Ordering::Greater
         );
-
         assert_eq!(
-            Hand([12, 12, 12, 12, 2])
-                .remap_joker()
-                .cmp(&Hand([13, 13, 13, 11, 2]).remap_joker()),
-            Ordering::Less
+            "size=7 categories=7,6+1".parse::<RuleSet>().unwrap_err(),
+            "categories must order every 7-card hand exactly once"
         );
 
         assert_eq!(
-            Hand([11, 12, 12, 12, 2])
-                .remap_joker()
-                .cmp(&Hand([13, 13, 13, 13, 2]).remap_joker()),
-            Ordering::Less
+            generator("AKQ 1\nAKQJ 2").unwrap_err(),
+            "hand 2 has 4 cards, expected 3"
         );
-
         assert_eq!(
-            Hand([11, 11, 11, 11, 11])
-                .remap_joker()
-                .cmp(&Hand([2, 2, 2, 2, 2]).remap_joker()),
-            Ordering::Less
+            generator("AKQ 1\nAKQ").unwrap_err(),
+            "expected `<hand> <bid>`, got `AKQ`"
         );
+        assert_eq!(
+            generator("AKQ x").unwrap_err(),
+            "invalid bid `x`: invalid digit found in string"
+        );
+        assert_eq!(generator("AKX 1").unwrap_err(), "card `X` has no label");
     }
 
     #[test]
-    fn test_part_1() {
-        let input = generator(&INPUT);
-        assert_eq!(part_1(&input), 6440);
-    }
-
-    #[test]
-    fn test_remap_joker() {
-        assert_eq!(Hand([0, 0, 0, 0, 0]).remap_joker(), Hand([0, 0, 0, 0, 0]));
-        assert_eq!(Hand([11, 0, 0, 0, 0]).remap_joker(), Hand([1, 0, 0, 0, 0]));
-        assert_eq!(Hand([11, 0, 1, 2, 3]).remap_joker(), Hand([1, 0, 1, 2, 3]));
+    fn test_standings() {
+        let input = generator(INPUT).unwrap();
+        let table = standings(&input, &RuleSet::jokers(5));
+        assert_eq!(table.total(), part_2(&input));
+        assert_snapshot!(table.to_string());
         assert_eq!(
-            Hand([11, 11, 11, 11, 11]).remap_joker(),
-            Hand([1, 1, 1, 1, 1])
+            table.csv(),
+            "rank,hand,category,effective,bid,winnings
+1,32T3K,pair,32T3K,765,765
+2,KK677,two-pair,KK677,28,56
+3,T55J5,four,T5555,684,2052
+4,QQQJA,four,QQQQA,483,1932
+5,KTJJT,four,KTTTT,220,1100"
         );
-    }
 
-    #[test]
-    fn test_part_2() {
-        let input = generator(&INPUT);
-        assert_eq!(part_2(&input), 5905);
+        let input = generator("JJJJJ 1\nKK2J2 2\n23456 3\nKK2J2 4\n23456 5").unwrap();
+        let tied = standings(&input, &RuleSet::jokers(5));
+        assert_eq!(tied.ties, vec![vec![1, 2], vec![3, 4]]);
+        assert_eq!(tied.rows[4].effective, parse_hand("AAAAA"));
+        assert_eq!(tied.rows[2].effective, parse_hand("KK2K2"));
+        assert!(tied.to_string().ends_with(
+            "tie: 23456 at ranks 1, 2
+tie: KK2J2 at ranks 3, 4"
+        ));
     }
 }
@@ -74,16 +74,162 @@ fn traverse_shortest(input: &Input, from: &str) -> usize {
     count
 }
 
-pub fn part_2(input: &Input) -> u128 {
-    let shortest: Vec<u128> = input
+fn is_end(key: &str) -> bool {
+    key.chars().nth(2).unwrap() == 'Z'
+}
+
+fn starts(input: &Input) -> Vec<&str> {
+    input
         .nodes
         .keys()
         .filter(|&key| matches!(key.chars().nth(2).unwrap(), 'A'))
-        .collect_vec()
+        .map(String::as_str)
+        .sorted()
+        .collect()
+}
+
+// A walker's path through (node, instruction index) states: after `start` steps it loops
+// every `period` steps, and it stands on a Z node at the steps in `before` and then at
+// `start + offset + k * period` for each offset.
+#[derive(Debug, PartialEq, Eq)]
+struct Cycle {
+    start: usize,
+    period: usize,
+    before: Vec<usize>,
+    offsets: Vec<usize>,
+}
+
+impl Cycle {
+    fn hits(&self, step: usize) -> bool {
+        if step < self.start {
+            self.before.contains(&step)
+        } else {
+            self.offsets.contains(&((step - self.start) % self.period))
+        }
+    }
+
+    // the official inputs: Z is reached exactly at every multiple of the period
+    fn is_simple(&self) -> bool {
+        self.before.iter().all(|&step| step == 0)
+            && self.start <= self.period
+            && self.offsets.len() == 1
+            && (self.start + self.offsets[0]).is_multiple_of(self.period)
+    }
+}
+
+fn analyse(input: &Input, from: &str) -> Cycle {
+    let mut seen = HashMap::new();
+    let mut hits = vec![];
+    let mut pos = from;
+    let mut step = 0;
+    loop {
+        let state = (pos, step % input.nav.len());
+        if let Some(&start) = seen.get(&state) {
+            let (before, looped): (Vec<_>, Vec<_>) = hits.into_iter().partition(|&hit| hit < start);
+            return Cycle {
+                start,
+                period: step - start,
+                before,
+                offsets: looped.iter().map(|hit| hit - start).collect(),
+            };
+        }
+        seen.insert(state, step);
+        if is_end(pos) {
+            hits.push(step);
+        }
+        pos = match input.nav[step % input.nav.len()] {
+            Nav::Left => &input.nodes[pos].0,
+            Nav::Right => &input.nodes[pos].1,
+        };
+        step += 1;
+    }
+}
+
+fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = ext_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+// Generalised CRT: x = a (mod m) and x = b (mod n) for moduli that need not be coprime,
+// as a single residue modulo lcm(m, n), if they agree at all.
+fn crt((a, m): (i128, i128), (b, n): (i128, i128)) -> Option<(i128, i128)> {
+    let (g, p, _) = ext_gcd(m, n);
+    if (b - a) % g != 0 {
+        return None;
+    }
+    let lcm = m / g * n;
+    let k = ((b - a) / g % (n / g) * p).rem_euclid(n / g);
+    Some(((a + m * k).rem_euclid(lcm), lcm))
+}
+
+// The first step, one or later, where every walker stands on a Z node.
+fn first_meeting(cycles: &[Cycle]) -> Option<u128> {
+    if cycles.iter().all(Cycle::is_simple) {
+        let periods = cycles
+            .iter()
+            .map(|cycle| cycle.period as u128)
+            .collect_vec();
+        return Some(lcmm(&periods));
+    }
+
+    // until every walker is looping, just walk
+    let settled = cycles
+        .iter()
+        .map(|cycle| cycle.start)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    if let Some(step) = (1..settled).find(|&step| cycles.iter().all(|cycle| cycle.hits(step))) {
+        return Some(step as u128);
+    }
+
+    let mut solutions = vec![(0, 1)];
+    for cycle in cycles {
+        solutions = solutions
+            .iter()
+            .flat_map(|&solution| {
+                cycle.offsets.iter().filter_map(move |&offset| {
+                    let residue = (cycle.start + offset) as i128;
+                    crt(solution, (residue, cycle.period as i128))
+                })
+            })
+            .unique()
+            .collect();
+    }
+    // the residues are already reduced, so only ones below `settled` need lifting
+    let settled = settled as u128;
+    solutions
+        .iter()
+        .map(|&(residue, modulus)| {
+            let (residue, modulus) = (residue as u128, modulus as u128);
+            residue + settled.saturating_sub(residue).div_ceil(modulus) * modulus
+        })
+        .min()
+}
+
+pub fn part_2(input: &Input) -> Result<u128, String> {
+    let cycles = starts(input)
         .par_iter()
-        .map(|pos| traverse_shortest(input, pos) as u128)
-        .collect();
-    lcmm(&shortest)
+        .map(|pos| analyse(input, pos))
+        .collect::<Vec<_>>();
+    first_meeting(&cycles).ok_or_else(|| "the ghosts never all reach Z at once".to_string())
+}
+
+pub fn cycles(input: &Input) -> String {
+    starts(input)
+        .iter()
+        .map(|&pos| {
+            let cycle = analyse(input, pos);
+            format!(
+                "{pos}: enters a {}-step loop after {} steps, Z at {:?} then at loop offsets {:?}",
+                cycle.period, cycle.start, cycle.before, cycle.offsets
+            )
+        })
+        .join("\n")
 }
 
 fn gcd(a: u128, b: u128) -> u128 {
@@ -120,6 +266,7 @@ mod tests {
     use itertools::Itertools;
 
     use super::*;
+    use crate::test_util::Lcg;
 
     static INPUT: &str = " RL
 
@@ -166,7 +313,81 @@ ZZZ = (ZZZ, ZZZ)";
 22Z = (22B, 22B)
 XXX = (XXX, XXX)";
         let input = generator(&input);
-        assert_eq!(part_2(&input), 6);
+        assert_eq!(part_2(&input), Ok(6));
+        assert_eq!(
+            starts(&input)
+                .iter()
+                .map(|pos| analyse(&input, pos))
+                .collect_vec(),
+            vec![
+                Cycle {
+                    start: 1,
+                    period: 2,
+                    before: vec![],
+                    offsets: vec![1],
+                },
+                Cycle {
+                    start: 1,
+                    period: 6,
+                    before: vec![],
+                    offsets: vec![2, 5],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt((2, 3), (3, 5)), Some((8, 15)));
+        assert_eq!(crt((2, 4), (4, 6)), Some((10, 12)));
+        assert_eq!(crt((1, 4), (2, 6)), None);
+        assert_eq!(crt((0, 1), (5, 7)), Some((5, 7)));
+    }
+
+    #[test]
+    fn test_first_meeting() {
+        // random little maps, against walking every ghost in lockstep
+        let mut rng = Lcg::new(0x9e37_79b9_7f4a_7c15);
+        let mut next = move |bound: usize| rng.below(bound as u64) as usize;
+        for _ in 0..300 {
+            let size = 2 + next(8);
+            let names = (0..size)
+                .map(|i| format!("{i:02}{}", ['A', 'Z', 'X'][next(3)]))
+                .collect_vec();
+            let nodes = names
+                .iter()
+                .map(|name| {
+                    let left = names[next(size)].clone();
+                    let right = names[next(size)].clone();
+                    (name.clone(), (left, right))
+                })
+                .collect();
+            let nav = (0..1 + next(4))
+                .map(|_| [Nav::Left, Nav::Right][next(2)])
+                .collect();
+            let input = Input { nav, nodes };
+
+            let mut ghosts = starts(&input);
+            let brute = (1..=10_000).find(|&step| {
+                let nav = input.nav[(step - 1) % input.nav.len()];
+                for ghost in ghosts.iter_mut() {
+                    *ghost = match nav {
+                        Nav::Left => &input.nodes[*ghost].0,
+                        Nav::Right => &input.nodes[*ghost].1,
+                    };
+                }
+                ghosts.iter().all(|ghost| is_end(ghost))
+            });
+
+            let cycles = starts(&input)
+                .iter()
+                .map(|pos| analyse(&input, pos))
+                .collect_vec();
+            match brute {
+                Some(step) => assert_eq!(first_meeting(&cycles), Some(step as u128), "{input:?}"),
+                None => assert!(first_meeting(&cycles).is_none_or(|step| step > 10_000)),
+            }
+        }
     }
 
     #[test]
@@ -9,17 +9,19 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+#[cfg(test)]
+mod test_util;
 
 aoc_main::main! {
     year 2023;
     day1 => part_1, part_2;
-    day2: generator => part_1, part_2;
-    day3 => part_1, part_2;
-    day4 => part_1, part_2;
-    day5: generator => part_1, part_2;
-    day6: generator => part_1, part_2;
-    day7: generator => part_1, part_2;
-    day8: generator => part_1, part_2;
+    day2: generator => part_1, part_2, report, estimate, query;
+    day3: generator => part_1, part_2?, rule, render, assembly;
+    day4: generator? => part_1, part_2, cascade_json, cascade_dot, variant;
+    day5: generator? => part_1, part_2, composed, inverse, graph, linted;
+    day6: generator => part_1, part_2, report;
+    day7: generator? => part_1, part_2, variant, table, csv;
+    day8: generator => part_1, part_2?, cycles;
     day9: generator => part_1, part_2;
     day10: generator => part_1, part_2;
     day11: generator => part_1, part_2;